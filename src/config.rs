@@ -1,14 +1,311 @@
+use crate::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Project config file names, looked up from the working directory upwards.
+const PROJECT_FILES: [&str; 2] = [".monkey-typer.toml", ".monkey-typer.json"];
+
+/// Settings that can come from the CLI, the environment or a config file.
+/// Unset fields fall through to the next source (CLI > env > project > user)
+/// and finally to the built-in defaults of the getters.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct AppConfig {
+    pub step_key: Option<String>,
+    pub pause_key: Option<String>,
+    pub start_key: Option<String>,
+    pub exit_key: Option<String>,
+    /// Step mode: which keys advance playback when air-typing.
+    pub air_keys: Option<String>,
+    /// Step mode: scripted actions played per air-typed key.
+    pub actions_per_key: Option<usize>,
+    /// Output backend: `os` or `humanize`.
+    pub backend: Option<String>,
+    /// Editor profile: `plain` or `autoindent`.
+    pub editor: Option<String>,
+    /// How `--loop` resets the target: `erase` or key combos.
+    pub reset: Option<String>,
     pub log_level: Option<String>,
+    /// Named output targets that `Target` events switch between.
+    pub targets: BTreeMap<String, TargetConfig>,
+}
+
+/// Where a named output target types: a Neovim server socket or a tmux
+/// pane. With neither set it is the OS keyboard, like the default output.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TargetConfig {
+    /// `nvim --listen` address, e.g. `/tmp/nvim.sock`.
+    pub nvim: Option<String>,
+    /// tmux pane, e.g. `demo:1.0`.
+    pub tmux: Option<String>,
+    /// Editor profile; defaults to the top-level `editor`.
+    pub editor: Option<String>,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self { log_level: None }
+impl TargetConfig {
+    /// Short description for `config show` and the startup banner.
+    pub fn describe(&self) -> String {
+        let mut out = match (&self.nvim, &self.tmux) {
+            (Some(socket), _) => format!("nvim {socket}"),
+            (None, Some(pane)) => format!("tmux {pane}"),
+            (None, None) => "os".to_string(),
+        };
+        if let Some(editor) = &self.editor {
+            out += &format!(" ({editor})");
+        }
+        out
     }
 }
 
-impl AppConfig {}
+/// One source of settings, named for `config show`.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub source: String,
+    pub config: AppConfig,
+}
+
+impl AppConfig {
+    /// Reads a `.toml` or `.json` config file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let name = path.display().to_string();
+        let text = std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("{name}: {e}"))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str(&text)
+                .map_err(|e| anyhow::anyhow!("{name}:{}:{}: {e}", e.line(), e.column())),
+            _ => toml::from_str(&text).map_err(|e| match e.span() {
+                Some(span) => {
+                    let (line, column) = line_column(&text, span.start);
+                    anyhow::anyhow!("{name}:{line}:{column}: {}", e.message())
+                }
+                None => anyhow::anyhow!("{name}: {}", e.message()),
+            }),
+        }
+    }
+
+    /// `MONKEY_TYPER_<FIELD>` variables; the log level is `MONKEY_TYPER_LOG`
+    /// or `RUST_LOG`.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|k| std::env::var(k).ok())
+    }
+
+    /// Only the log level from the environment, for commands that take no
+    /// other settings.
+    pub fn log_from_env() -> Self {
+        Self { log_level: log_var(|k| std::env::var(k).ok()), ..Self::default() }
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let get = |field: &str| var(&format!("MONKEY_TYPER_{}", field.to_ascii_uppercase()));
+        let actions_per_key = match get("actions_per_key") {
+            Some(v) => Some(v.parse().map_err(|e| anyhow::anyhow!("MONKEY_TYPER_ACTIONS_PER_KEY={v:?}: {e}"))?),
+            None => None,
+        };
+        Ok(Self {
+            step_key: get("step_key"),
+            pause_key: get("pause_key"),
+            start_key: get("start_key"),
+            exit_key: get("exit_key"),
+            air_keys: get("air_keys"),
+            actions_per_key,
+            backend: get("backend"),
+            editor: get("editor"),
+            reset: get("reset"),
+            log_level: log_var(&var),
+            targets: BTreeMap::new(),
+        })
+    }
+
+    /// Fields set here win, the rest come from `lower`. Targets are merged
+    /// by name, a target defined here replacing one of the same name.
+    pub fn or(self, lower: Self) -> Self {
+        let mut targets = lower.targets;
+        targets.extend(self.targets);
+        Self {
+            step_key: self.step_key.or(lower.step_key),
+            pause_key: self.pause_key.or(lower.pause_key),
+            start_key: self.start_key.or(lower.start_key),
+            exit_key: self.exit_key.or(lower.exit_key),
+            air_keys: self.air_keys.or(lower.air_keys),
+            actions_per_key: self.actions_per_key.or(lower.actions_per_key),
+            backend: self.backend.or(lower.backend),
+            editor: self.editor.or(lower.editor),
+            reset: self.reset.or(lower.reset),
+            log_level: self.log_level.or(lower.log_level),
+            targets,
+        }
+    }
+
+    /// Merges layers, highest precedence first.
+    pub fn resolve(layers: &[Layer]) -> Self {
+        layers.iter().fold(Self::default(), |acc, l| acc.or(l.config.clone()))
+    }
+
+    pub fn step_key(&self) -> &str { self.step_key.as_deref().unwrap_or("right") }
+    pub fn pause_key(&self) -> &str { self.pause_key.as_deref().unwrap_or("ctrl+p") }
+    pub fn start_key(&self) -> &str { self.start_key.as_deref().unwrap_or("ctrl+s") }
+    pub fn exit_key(&self) -> &str { self.exit_key.as_deref().unwrap_or("ctrl+q") }
+    pub fn air_keys(&self) -> &str { self.air_keys.as_deref().unwrap_or("printable") }
+    pub fn actions_per_key(&self) -> usize { self.actions_per_key.unwrap_or(1).max(1) }
+    pub fn backend(&self) -> &str { self.backend.as_deref().unwrap_or("os") }
+    pub fn editor(&self) -> &str { self.editor.as_deref().unwrap_or("plain") }
+    pub fn reset(&self) -> &str { self.reset.as_deref().unwrap_or("erase") }
+    pub fn log_level(&self) -> &str { self.log_level.as_deref().unwrap_or("info") }
+
+    /// Values set in this layer, by field name.
+    fn set_fields(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("step_key", self.step_key.clone()),
+            ("pause_key", self.pause_key.clone()),
+            ("start_key", self.start_key.clone()),
+            ("exit_key", self.exit_key.clone()),
+            ("air_keys", self.air_keys.clone()),
+            ("actions_per_key", self.actions_per_key.map(|n| n.to_string())),
+            ("backend", self.backend.clone()),
+            ("editor", self.editor.clone()),
+            ("reset", self.reset.clone()),
+            ("log_level", self.log_level.clone()),
+        ]
+    }
+
+    /// Effective values with defaults filled in, by field name.
+    fn effective(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("step_key", self.step_key().to_string()),
+            ("pause_key", self.pause_key().to_string()),
+            ("start_key", self.start_key().to_string()),
+            ("exit_key", self.exit_key().to_string()),
+            ("air_keys", self.air_keys().to_string()),
+            ("actions_per_key", self.actions_per_key().to_string()),
+            ("backend", self.backend().to_string()),
+            ("editor", self.editor().to_string()),
+            ("reset", self.reset().to_string()),
+            ("log_level", self.log_level().to_string()),
+        ]
+    }
+}
+
+/// The effective configuration as TOML, each value annotated with the layer
+/// it came from.
+pub fn show(layers: &[Layer]) -> String {
+    let effective = AppConfig::resolve(layers);
+    let set: Vec<_> = layers.iter().map(|l| l.config.set_fields()).collect();
+    let mut out = String::new();
+    for (i, (field, value)) in effective.effective().into_iter().enumerate() {
+        let source = layers
+            .iter()
+            .zip(&set)
+            .find(|(_, fields)| fields[i].1.is_some())
+            .map_or("default", |(l, _)| l.source.as_str());
+        let value = match field {
+            "actions_per_key" => value,
+            _ => format!("{value:?}"),
+        };
+        out += &format!("{:<32} # {source}\n", format!("{field} = {value}"));
+    }
+    for (name, target) in &effective.targets {
+        let source = layers.iter().find(|l| l.config.targets.contains_key(name)).map_or("default", |l| l.source.as_str());
+        out += &format!("{:<32} # {source}\n", format!("targets.{name} = {:?}", target.describe()));
+    }
+    out
+}
+
+fn log_var(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    var("MONKEY_TYPER_LOG").or_else(|| var("RUST_LOG"))
+}
+
+/// 1-based line and column of byte `offset` in `text`.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+/// Project files in every directory from `cwd` up, then the user file,
+/// highest precedence first.
+pub fn discover(cwd: &Path) -> Result<Vec<Layer>> {
+    let mut layers = Vec::new();
+    let project = cwd.ancestors().flat_map(|dir| PROJECT_FILES.iter().map(move |f| dir.join(f)));
+    for path in project.filter(|p| p.is_file()).chain(user_file()) {
+        layers.push(Layer { config: AppConfig::load(&path)?, source: path.display().to_string() });
+    }
+    Ok(layers)
+}
+
+/// `$XDG_CONFIG_HOME/monkey-typer/config.{toml,json}`, or under `~/.config`.
+fn user_file() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    let dir = base.join("monkey-typer");
+    ["config.toml", "config.json"].iter().map(|f| dir.join(f)).find(|p| p.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_merge_in_precedence_order() {
+        let dir = std::env::temp_dir().join(format!("monkey-typer-config-{}", std::process::id()));
+        let nested = dir.join("talk/scripts");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(dir.join(".monkey-typer.toml"), "step_key = \"space\"\nbackend = \"humanize\"\nactions_per_key = 3\n").unwrap();
+        std::fs::write(dir.join("talk/.monkey-typer.json"), r#"{"step_key": "f13", "editor": "autoindent"}"#).unwrap();
+
+        let mut found = discover(&nested).unwrap();
+        assert_eq!(found[1].source, dir.join(".monkey-typer.toml").display().to_string());
+        let project = found.remove(0);
+        assert!(project.source.ends_with(".monkey-typer.json"));
+        let env = AppConfig::from_vars(|k| match k {
+            "MONKEY_TYPER_BACKEND" => Some("os".into()),
+            "RUST_LOG" => Some("debug".into()),
+            _ => None,
+        })
+        .unwrap();
+        let user = AppConfig::load(&dir.join(".monkey-typer.toml")).unwrap();
+        let cli = AppConfig { pause_key: Some("f1".into()), ..Default::default() };
+        let layers = [
+            Layer { source: "cli".into(), config: cli },
+            Layer { source: "env".into(), config: env },
+            project,
+            Layer { source: "user".into(), config: user },
+        ];
+        let cfg = AppConfig::resolve(&layers);
+        assert_eq!(
+            (cfg.step_key(), cfg.pause_key(), cfg.exit_key(), cfg.backend(), cfg.editor(), cfg.actions_per_key()),
+            ("f13", "f1", "ctrl+q", "os", "autoindent", 3)
+        );
+        assert_eq!(cfg.log_level(), "debug");
+        let shown = show(&layers);
+        assert!(shown.contains("pause_key = \"f1\""), "{shown}");
+        assert!(shown.lines().any(|l| l.starts_with("actions_per_key = 3") && l.ends_with("# user")), "{shown}");
+        assert!(shown.lines().any(|l| l.starts_with("exit_key") && l.ends_with("# default")), "{shown}");
+
+        std::fs::write(dir.join("bad.json"), r#"{"step": "x"}"#).unwrap();
+        assert!(AppConfig::load(&dir.join("bad.json")).unwrap_err().to_string().contains("unknown field"));
+        std::fs::write(dir.join("bad.json"), r#"{"targets": {"shell": {"pane": "x"}}}"#).unwrap();
+        assert!(AppConfig::load(&dir.join("bad.json")).unwrap_err().to_string().contains("unknown field"));
+        std::fs::write(dir.join("bad.toml"), "step_key = \"x\"\nstep = 1\n").unwrap();
+        let err = AppConfig::load(&dir.join("bad.toml")).unwrap_err().to_string();
+        assert!(err.contains("bad.toml:2:1: unknown field `step`"), "{err}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn targets_merge_by_name() {
+        let user: AppConfig = toml::from_str(
+            "[targets.editor]\nnvim = \"/tmp/a.sock\"\n[targets.shell]\ntmux = \"demo:0\"\n",
+        )
+        .unwrap();
+        let project: AppConfig = toml::from_str("[targets.shell]\ntmux = \"demo:1\"\neditor = \"autoindent\"\n").unwrap();
+        let layers = [Layer { source: "project".into(), config: project }, Layer { source: "user".into(), config: user }];
+        let cfg = AppConfig::resolve(&layers);
+        assert_eq!(cfg.targets["editor"].nvim.as_deref(), Some("/tmp/a.sock"));
+        assert_eq!(cfg.targets["shell"].tmux.as_deref(), Some("demo:1"));
+        let shown = show(&layers);
+        assert!(shown.lines().any(|l| l.starts_with("targets.editor = \"nvim /tmp/a.sock\"") && l.ends_with("# user")), "{shown}");
+        assert!(shown.lines().any(|l| l.starts_with("targets.shell = \"tmux demo:1 (autoindent)\"") && l.ends_with("# project")), "{shown}");
+    }
+}
@@ -0,0 +1,707 @@
+use super::{
+    engine::Engine,
+    persist::StateFile,
+    stats::{LatencyStats, Sample},
+    status::{Status, StatusHub},
+};
+use crate::io::trigger::{TriggerEvent, TriggerSource};
+use crate::output::reset::Reset;
+use crate::{output::injector::OutputInjector, prelude::*};
+use std::time::{Duration, Instant};
+
+/// Whether the controller should keep consuming trigger events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Done,
+    Exit,
+}
+
+/// Turns `TriggerEvent`s into engine steps, independent of where they come from.
+pub struct Controller<I: OutputInjector> {
+    pub engine: Engine,
+    pub injector: I,
+    pub started: bool,
+    pub stats: LatencyStats,
+    pub hub: StatusHub,
+    /// Scripted actions played per air-typed key.
+    pub actions_per_key: usize,
+    pub state: Option<StateFile>,
+    /// Set while a full-screen view owns the terminal; messages are only kept
+    /// in `message` instead of being printed.
+    pub quiet: bool,
+    pub message: Option<String>,
+    pub started_at: Option<Instant>,
+    received_at: Instant,
+    shown_note: Option<usize>,
+    shown_branch: Option<usize>,
+}
+
+const REFRESH: Duration = Duration::from_millis(250);
+const PAUSED_POLL: Duration = Duration::from_millis(50);
+
+/// Settings for [`Controller::run_loop`].
+#[derive(Debug, Clone)]
+pub struct LoopOptions {
+    /// Delay between actions while autoplaying.
+    pub interval: Duration,
+    /// How long the finished text stays up before the reset.
+    pub wait: Duration,
+    pub reset: Reset,
+}
+
+impl<I: OutputInjector> Controller<I> {
+    pub fn new(engine: Engine, injector: I) -> Self {
+        let ctl = Self {
+            engine,
+            injector,
+            started: false,
+            stats: LatencyStats::default(),
+            hub: StatusHub::new(),
+            actions_per_key: 1,
+            state: None,
+            quiet: false,
+            message: None,
+            started_at: None,
+            received_at: Instant::now(),
+            shown_note: None,
+            shown_branch: None,
+        };
+        ctl.hub.publish(ctl.status());
+        ctl
+    }
+
+    pub fn run<S: TriggerSource + ?Sized>(&mut self, src: &S) -> Result<Flow> {
+        while let Some((ev, at)) = src.recv_stamped(None) {
+            let flow = self.handle_at(ev, at)?;
+            if flow != Flow::Continue {
+                return Ok(flow);
+            }
+        }
+        Ok(Flow::Exit)
+    }
+
+    /// Like `run`, calling `redraw` after every event and at least every
+    /// `REFRESH` so time-based displays stay current.
+    pub fn run_with<S, F>(&mut self, src: &S, mut redraw: F) -> Result<Flow>
+    where
+        S: TriggerSource + ?Sized,
+        F: FnMut(&Self) -> Result<()>,
+    {
+        redraw(self)?;
+        loop {
+            if let Some((ev, at)) = src.recv_stamped(Some(REFRESH)) {
+                let flow = self.handle_at(ev, at)?;
+                redraw(self)?;
+                if flow != Flow::Continue {
+                    return Ok(flow);
+                }
+            } else {
+                redraw(self)?;
+            }
+        }
+    }
+
+    /// Kiosk mode: autoplays to EOF, waits, resets the target and starts
+    /// over until an `Exit` arrives. Branches take their default path. An
+    /// injector error ends the round early instead of stopping playback.
+    /// Only pause, resume and exit are taken from `src`.
+    pub fn run_loop<S: TriggerSource + ?Sized>(&mut self, src: &S, opts: &LoopOptions) -> Result<Flow> {
+        self.started = true;
+        self.started_at = Some(Instant::now());
+        let mut round = 0;
+        loop {
+            round += 1;
+            info!("loop round {}", round);
+            let played = self.autoplay(src, opts.interval);
+            match played {
+                Ok(Flow::Exit) => return Ok(Flow::Exit),
+                Ok(_) => {}
+                Err(e) => warn!("round {} failed at {}: {:#}", round, self.engine.head.pos, e),
+            }
+            if self.idle(src, opts.wait)? == Flow::Exit {
+                return Ok(Flow::Exit);
+            }
+            if let Err(e) = self.reset_target(&opts.reset) {
+                warn!("reset failed: {:#}", e);
+            }
+            self.engine.reset();
+            self.hub.publish(self.status());
+        }
+    }
+
+    /// Plays to EOF, one action per `interval`. While paused it waits at
+    /// least `PAUSED_POLL` between checks, even with a zero interval.
+    fn autoplay<S: TriggerSource + ?Sized>(&mut self, src: &S, interval: Duration) -> Result<Flow> {
+        while !self.engine.head.is_eof() {
+            let wait = if self.engine.head.paused { interval.max(PAUSED_POLL) } else { interval };
+            if self.idle(src, wait)? == Flow::Exit {
+                return Ok(Flow::Exit);
+            }
+            if self.engine.head.paused {
+                continue;
+            }
+            match self.engine.awaiting() {
+                Some(b) => self.engine.choose(b, 0),
+                None => self.engine.step(&mut self.injector)?,
+            }
+            self.hub.publish(self.status());
+        }
+        Ok(Flow::Done)
+    }
+
+    /// Handles pause, resume and exit events for `wait`; other events are
+    /// ignored so visitors cannot derail the loop.
+    fn idle<S: TriggerSource + ?Sized>(&mut self, src: &S, wait: Duration) -> Result<Flow> {
+        let deadline = Instant::now() + wait;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if let Some((ev, at)) = src.recv_stamped(Some(left)) {
+                use TriggerEvent::{Exit, Pause, PauseToggle, Resume};
+                if !matches!(ev, Exit | Pause | Resume | PauseToggle) {
+                    debug!("loop: ignoring {:?}", ev);
+                } else if self.handle_at(ev, at)? == Flow::Exit {
+                    return Ok(Flow::Exit);
+                }
+            }
+            if left.is_zero() {
+                return Ok(Flow::Continue);
+            }
+        }
+    }
+
+    /// Puts every output target back to its initial state.
+    fn reset_target(&mut self, reset: &Reset) -> Result<()> {
+        for (target, text) in self.engine.texts() {
+            self.injector.target(target)?;
+            match reset {
+                Reset::Erase => {
+                    for _ in 0..text.chars().count() {
+                        self.injector.backspace()?;
+                    }
+                }
+                Reset::Keys(combos) => {
+                    for combo in combos {
+                        self.injector.combo(combo)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn handle(&mut self, ev: TriggerEvent) -> Result<Flow> {
+        self.handle_at(ev, Instant::now())
+    }
+
+    /// Handles `ev`, produced by its source at `at`; the time until it is
+    /// played counts as queueing latency.
+    pub fn handle_at(&mut self, ev: TriggerEvent, at: Instant) -> Result<Flow> {
+        self.received_at = at;
+        let flow = self.dispatch(ev)?;
+        if self.started {
+            self.show_notes();
+            self.show_branch();
+        }
+        self.hub.publish(self.status());
+        if let Some(state) = &mut self.state {
+            let route = self.engine.route();
+            let saved = match flow {
+                Flow::Continue => state.maybe_save(self.engine.head.pos, &route),
+                _ => state.save(self.engine.head.pos, &route),
+            };
+            if let Err(e) = saved {
+                warn!("cannot save the playhead to {}: {:#}", state.path().display(), e);
+            }
+        }
+        Ok(flow)
+    }
+
+    pub fn status(&self) -> Status {
+        let branch = self.engine.awaiting().map(|b| &self.engine.branches[b]);
+        Status {
+            started: self.started,
+            paused: self.engine.head.paused,
+            pos: self.engine.head.pos,
+            end: self.engine.head.end,
+            marker: self.engine.current_marker().map(str::to_string),
+            note: self.engine.current_note().map(|i| self.engine.notes[i].1.clone()),
+            next_note: self.engine.next_note().map(|i| self.engine.notes[i].1.clone()),
+            branch: branch.map(|b| b.name.clone()),
+            choices: branch.map_or_else(Vec::new, |b| b.paths.iter().map(|(name, _)| name.clone()).collect()),
+            target: Some(self.engine.target_at(self.engine.head.pos)).filter(|t| !t.is_empty()).map(str::to_string),
+        }
+    }
+
+    fn dispatch(&mut self, ev: TriggerEvent) -> Result<Flow> {
+        match ev {
+            TriggerEvent::Start => {
+                if !self.started {
+                    self.started = true;
+                    self.started_at = Some(Instant::now());
+                    self.say("[Started]");
+                }
+            }
+            TriggerEvent::Step => return self.play(1),
+            TriggerEvent::AirKey { erase } => {
+                if !self.started {
+                    return Ok(Flow::Continue);
+                }
+                if erase {
+                    self.injector.backspace()?;
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+                return self.play(self.actions_per_key);
+            }
+            TriggerEvent::Back => {
+                if self.started {
+                    self.engine.back(&mut self.injector)?;
+                }
+            }
+            TriggerEvent::PauseToggle => {
+                self.engine.toggle_pause();
+                self.print_pause_state();
+            }
+            TriggerEvent::Pause | TriggerEvent::Resume => {
+                self.engine.set_paused(ev == TriggerEvent::Pause);
+                self.print_pause_state();
+            }
+            TriggerEvent::Seek(pos) => self.seek(pos)?,
+            TriggerEvent::GotoMarker(name) => match self.engine.marker_pos(&name) {
+                Some(pos) => self.seek(pos)?,
+                None => self.say(&format!("\r[Unknown marker: {}]", name)),
+            },
+            TriggerEvent::Skip => {
+                if !self.started {
+                    return Ok(Flow::Continue);
+                }
+                // like stepping, skipping waits for playback to resume
+                if self.engine.head.paused {
+                    self.say("\r[Paused]");
+                    return Ok(Flow::Continue);
+                }
+                match self.engine.skip(&mut self.injector)? {
+                    Some(name) => self.say(&format!("\r[Skipped {}]", name)),
+                    None => self.say("\r[Not in an optional section]"),
+                }
+                if self.engine.head.is_eof() {
+                    self.say("\n[Done]");
+                    return Ok(Flow::Done);
+                }
+            }
+            TriggerEvent::Choose(which) => match self.engine.awaiting() {
+                None => self.say("\r[No branch to choose here]"),
+                Some(b) => match self.engine.path_index(b, &which) {
+                    Some(k) => {
+                        let branch = &self.engine.branches[b];
+                        let msg = format!("\r[{}: {}]", branch.name, branch.paths[k].0);
+                        self.engine.choose(b, k);
+                        self.say(&msg);
+                    }
+                    None => self.say(&format!("\r[Unknown path: {}]", which)),
+                },
+            },
+            TriggerEvent::Exit => {
+                self.say("\n[Exit]");
+                return Ok(Flow::Exit);
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Before `Start` only the playhead moves; nothing is typed.
+    fn seek(&mut self, pos: usize) -> Result<()> {
+        if self.started {
+            self.engine.seek(pos, &mut self.injector)
+        } else {
+            let route = self.engine.route();
+            self.engine.restore(pos, &route);
+            Ok(())
+        }
+    }
+
+    fn play(&mut self, n: usize) -> Result<Flow> {
+        if !self.started {
+            return Ok(Flow::Continue);
+        }
+        let (t0, from) = (Instant::now(), self.engine.head.pos);
+        for _ in 0..n {
+            self.engine.step(&mut self.injector)?;
+        }
+        // paused, at a branch or at EOF nothing was typed, so nothing to time
+        if self.engine.head.pos != from {
+            let sample = Sample::new(self.injector.name(), t0.saturating_duration_since(self.received_at), t0.elapsed());
+            self.stats.record(sample);
+        }
+        if self.engine.head.is_eof() {
+            self.say("\n[Done]");
+            return Ok(Flow::Done);
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Teleprompter: prints the current and upcoming speaker note whenever the
+    /// playhead moves into a new note.
+    fn show_notes(&mut self) {
+        let current = self.engine.current_note();
+        if current == self.shown_note {
+            return;
+        }
+        self.shown_note = current;
+        if self.quiet {
+            return;
+        }
+        if let Some(i) = current {
+            println!("\r\n[Note] {}", self.engine.notes[i].1);
+        }
+        if let Some(i) = self.engine.next_note() {
+            println!("\r  next: {}", self.engine.notes[i].1);
+        }
+    }
+
+    /// Lists the paths of a branch once the playhead reaches it.
+    fn show_branch(&mut self) {
+        let current = self.engine.awaiting();
+        if current == self.shown_branch {
+            return;
+        }
+        self.shown_branch = current;
+        let Some(b) = current else { return };
+        let branch = &self.engine.branches[b];
+        let paths: Vec<String> = branch.paths.iter().enumerate().map(|(k, (name, _))| format!("{}) {}", k + 1, name)).collect();
+        let msg = format!("\r\n[Branch {}: {}]", branch.name, paths.join("  "));
+        self.say(&msg);
+    }
+
+    fn print_pause_state(&mut self) {
+        let state = if self.engine.head.paused { "Paused" } else { "Running" };
+        self.say(&format!("\r[{}]   ", state));
+    }
+
+    fn say(&mut self, msg: &str) {
+        if !self.quiet {
+            println!("{}", msg);
+        }
+        self.message = Some(msg.trim().trim_matches(|c| c == '[' || c == ']').to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tks_json::Action;
+
+    #[derive(Default)]
+    struct Recorder {
+        out: String,
+        pastes: Vec<String>,
+    }
+
+    impl OutputInjector for Recorder {
+        fn send_char(&mut self, ch: char) -> Result<()> {
+            self.out.push(ch);
+            Ok(())
+        }
+        fn backspace(&mut self) -> Result<()> {
+            self.out.pop();
+            Ok(())
+        }
+        fn paste(&mut self, text: &str) -> Result<()> {
+            self.out += text;
+            self.pastes.push(text.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn steps_only_after_start_and_stops_at_eof() {
+        let engine = Engine::new(vec![Action::Char('a'), Action::Char('b')], vec![]);
+        let mut c = Controller::new(engine, Recorder::default());
+        let step = TriggerEvent::Step;
+
+        assert_eq!(c.handle(step.clone()).unwrap(), Flow::Continue);
+        assert_eq!(c.injector.out, "");
+        c.handle(TriggerEvent::Start).unwrap();
+        assert_eq!(c.handle(step.clone()).unwrap(), Flow::Continue);
+        assert_eq!(c.handle(step.clone()).unwrap(), Flow::Done);
+        assert_eq!(c.injector.out, "ab");
+        assert_eq!(c.stats.total().map(|s| s.count), Some(2));
+        c.handle(step.clone()).unwrap();
+        assert_eq!(c.stats.total().map(|s| s.count), Some(2));
+
+        let queued = Instant::now() - Duration::from_millis(40);
+        c.handle_at(TriggerEvent::Back, queued).unwrap();
+        c.handle_at(step.clone(), queued).unwrap();
+        assert!(c.stats.last().unwrap().queue_ms >= 40.0);
+        assert_eq!(c.injector.out, "ab");
+
+        c.handle(TriggerEvent::Back).unwrap();
+        assert_eq!(c.injector.out, "a");
+        assert_eq!(c.engine.head.pos, 1);
+    }
+
+    #[test]
+    fn air_keys_erase_leaks_and_play_several_actions() {
+        let acts = "hello".chars().map(Action::Char).collect::<Vec<_>>();
+        let mut c = Controller::new(Engine::new(acts, vec![]), Recorder::default());
+        c.actions_per_key = 2;
+        c.handle(TriggerEvent::Start).unwrap();
+
+        c.handle(TriggerEvent::AirKey { erase: false }).unwrap();
+        assert_eq!(c.injector.out, "he");
+        c.injector.out.push('x'); // key leaked through a listen-only backend
+        c.handle(TriggerEvent::AirKey { erase: true }).unwrap();
+        assert_eq!(c.injector.out, "hell");
+        assert_eq!(c.handle(TriggerEvent::AirKey { erase: false }).unwrap(), Flow::Done);
+        assert_eq!(c.injector.out, "hello");
+    }
+
+    #[test]
+    fn status_tracks_current_and_next_note() {
+        let acts = "abcd".chars().map(Action::Char).collect::<Vec<_>>();
+        let mut engine = Engine::new(acts, vec![]);
+        engine.notes = vec![(0, "intro".into()), (2, "explain c".into())];
+        let mut c = Controller::new(engine, Recorder::default());
+
+        c.handle(TriggerEvent::Start).unwrap();
+        assert_eq!(c.hub.latest().note.as_deref(), Some("intro"));
+        assert_eq!(c.hub.latest().next_note.as_deref(), Some("explain c"));
+        c.handle(TriggerEvent::Step).unwrap();
+        c.handle(TriggerEvent::Step).unwrap();
+        assert_eq!(c.hub.latest().note.as_deref(), Some("explain c"));
+        assert_eq!(c.hub.latest().next_note, None);
+    }
+
+    #[test]
+    fn branches_wait_for_a_choice_and_back_retraces_it() {
+        let json = br#"{"version":"2","tracks":[{"id":"main","sequence":[
+            {"type":"Insert","text":"a"},
+            {"type":"Branch","name":"depth","paths":[
+                {"name":"quick","sequence":[{"type":"Insert","text":"q"}]},
+                {"name":"deep","sequence":[{"type":"Insert","text":"dd"}]}
+            ]},
+            {"type":"Insert","text":"!"}
+        ]}]}"#;
+        let seq = crate::format::tks_json::Sequence::from_bytes(json).unwrap();
+        let mut engine = Engine::new(seq.actions, seq.markers);
+        engine.branches = seq.branches;
+        let mut c = Controller::new(engine, Recorder::default());
+        c.quiet = true;
+        c.handle(TriggerEvent::Start).unwrap();
+        let preview = |c: &Controller<Recorder>| {
+            c.engine.upcoming(10).into_iter().map(|a| match a {
+                Action::Char(ch) => ch,
+                Action::Backspace => '<',
+            }).collect::<String>()
+        };
+        assert_eq!(preview(&c), "aq!");
+
+        c.handle(TriggerEvent::Step).unwrap();
+        c.handle(TriggerEvent::Step).unwrap();
+        assert_eq!(c.injector.out, "a");
+        assert_eq!(c.hub.latest().choices, ["quick", "deep"]);
+        c.handle(TriggerEvent::Choose("nope".into())).unwrap();
+        assert_eq!(c.message.as_deref(), Some("Unknown path: nope"));
+        c.handle(TriggerEvent::Choose("deep".into())).unwrap();
+        assert_eq!(c.hub.latest().branch, None);
+        assert_eq!(preview(&c), "dd!");
+        for _ in 0..2 {
+            c.handle(TriggerEvent::Step).unwrap();
+        }
+        assert_eq!(c.handle(TriggerEvent::Step).unwrap(), Flow::Done);
+        assert_eq!((c.injector.out.as_str(), c.engine.shadow.text()), ("add!", "add!"));
+        assert_eq!(c.engine.route(), [1]);
+
+        // back through the path and the choice, then take the other one
+        for _ in 0..4 {
+            c.handle(TriggerEvent::Back).unwrap();
+        }
+        assert_eq!((c.injector.out.as_str(), c.engine.head.pos), ("a", 1));
+        assert_eq!(c.hub.latest().branch.as_deref(), Some("depth"));
+        c.handle(TriggerEvent::Choose("1".into())).unwrap();
+        c.handle(TriggerEvent::Step).unwrap();
+        assert_eq!(c.handle(TriggerEvent::Step).unwrap(), Flow::Done);
+        assert_eq!(c.engine.shadow.text(), "aq!");
+
+        let mut resumed = Engine::new(c.engine.actions.clone(), vec![]);
+        resumed.branches = c.engine.branches.clone();
+        resumed.restore(c.engine.head.pos, &c.engine.route());
+        assert_eq!((resumed.shadow.text(), resumed.head.pos), ("aq!", 5));
+    }
+
+    #[test]
+    fn skipping_an_optional_section_applies_its_net_effect() {
+        let json = br#"{"version":"2","tracks":[{"id":"main","sequence":[
+            {"type":"Insert","text":"hello"},
+            {"type":"Marker","name":"aside","optional":true},
+            {"type":"Insert","text":" world"},
+            {"type":"Delete","n":8},
+            {"type":"Insert","text":"p!"},
+            {"type":"Marker","name":"end"},
+            {"type":"Insert","text":"."}
+        ]}]}"#;
+        let seq = crate::format::tks_json::Sequence::from_bytes(json).unwrap();
+        let mut engine = Engine::new(seq.actions, seq.markers);
+        engine.optional = seq.optional;
+        let mut c = Controller::new(engine, Recorder::default());
+        c.quiet = true;
+        c.handle(TriggerEvent::Start).unwrap();
+
+        c.handle(TriggerEvent::Skip).unwrap();
+        assert_eq!(c.message.as_deref(), Some("Not in an optional section"));
+        for _ in 0..7 {
+            c.handle(TriggerEvent::Step).unwrap();
+        }
+        assert_eq!(c.injector.out, "hello w");
+        c.handle(TriggerEvent::Pause).unwrap();
+        c.handle(TriggerEvent::Skip).unwrap();
+        assert_eq!((c.injector.out.as_str(), c.message.as_deref()), ("hello w", Some("Paused")));
+        c.handle(TriggerEvent::Resume).unwrap();
+        c.handle(TriggerEvent::Skip).unwrap();
+        assert_eq!((c.injector.out.as_str(), c.engine.shadow.text()), ("help!", "help!"));
+        assert_eq!(c.injector.pastes, ["p!"]);
+        assert_eq!(c.hub.latest().marker.as_deref(), Some("end"));
+        assert_eq!(c.handle(TriggerEvent::Step).unwrap(), Flow::Done);
+        assert_eq!(c.injector.out, "help!.");
+    }
+
+    #[test]
+    fn targets_get_their_own_output_and_shadow() {
+        #[derive(Default)]
+        struct Targets {
+            outs: std::collections::BTreeMap<String, String>,
+            current: String,
+        }
+        impl OutputInjector for Targets {
+            fn send_char(&mut self, ch: char) -> Result<()> {
+                self.outs.entry(self.current.clone()).or_default().push(ch);
+                Ok(())
+            }
+            fn backspace(&mut self) -> Result<()> {
+                self.outs.entry(self.current.clone()).or_default().pop();
+                Ok(())
+            }
+            fn target(&mut self, name: &str) -> Result<()> {
+                self.current = name.to_string();
+                Ok(())
+            }
+        }
+        let json = br#"{"version":"2","tracks":[{"id":"main","sequence":[
+            {"type":"Insert","text":"ab"},
+            {"type":"Target","name":"shell"},
+            {"type":"Insert","text":"ls"},
+            {"type":"Target","name":""},
+            {"type":"Insert","text":"c"}
+        ]}]}"#;
+        let seq = crate::format::tks_json::Sequence::from_bytes(json).unwrap();
+        let mut engine = Engine::new(seq.actions, seq.markers);
+        engine.targets = seq.targets;
+        let mut c = Controller::new(engine, Targets::default());
+        c.quiet = true;
+        c.handle(TriggerEvent::Start).unwrap();
+        for _ in 0..3 {
+            c.handle(TriggerEvent::Step).unwrap();
+        }
+        assert_eq!(c.hub.latest().target.as_deref(), Some("shell"));
+        c.handle(TriggerEvent::Step).unwrap();
+        c.handle(TriggerEvent::Step).unwrap();
+        assert_eq!(c.engine.texts(), [("", "abc"), ("shell", "ls")]);
+        assert_eq!(c.hub.latest().target, None);
+
+        for _ in 0..2 {
+            c.handle(TriggerEvent::Back).unwrap();
+        }
+        assert_eq!(c.engine.texts(), [("", "ab"), ("shell", "l")]);
+        assert_eq!((c.injector.outs[""].as_str(), c.injector.outs["shell"].as_str()), ("ab", "l"));
+        c.reset_target(&Reset::Erase).unwrap();
+        assert!(c.injector.outs.values().all(String::is_empty));
+    }
+
+    #[test]
+    fn loop_resets_between_rounds_and_survives_injector_errors() {
+        /// Fails the 4th character, logs backspaces as `<`.
+        #[derive(Default)]
+        struct Flaky {
+            log: String,
+            chars: usize,
+        }
+        impl OutputInjector for Flaky {
+            fn send_char(&mut self, ch: char) -> Result<()> {
+                self.chars += 1;
+                if self.chars == 4 {
+                    anyhow::bail!("display went away");
+                }
+                self.log.push(ch);
+                Ok(())
+            }
+            fn backspace(&mut self) -> Result<()> {
+                self.log.push('<');
+                Ok(())
+            }
+        }
+        /// Exits after a number of polls.
+        struct Countdown(std::cell::Cell<usize>);
+        impl TriggerSource for Countdown {
+            fn recv_stamped(&self, _: Option<Duration>) -> Option<(TriggerEvent, Instant)> {
+                self.0.set(self.0.get().saturating_sub(1));
+                (self.0.get() == 0).then(|| (TriggerEvent::Exit, Instant::now()))
+            }
+        }
+
+        let engine = Engine::new(vec![Action::Char('a'), Action::Char('b')], vec![]);
+        let mut c = Controller::new(engine, Flaky::default());
+        c.quiet = true;
+        let opts = LoopOptions { interval: Duration::ZERO, wait: Duration::ZERO, reset: Reset::Erase };
+        assert_eq!(c.run_loop(&Countdown(std::cell::Cell::new(20)), &opts).unwrap(), Flow::Exit);
+        assert!(c.injector.log.starts_with("ab<<a<ab<<ab<<"), "{}", c.injector.log);
+
+        let mut c = Controller::new(Engine::new(vec![Action::Char('a')], vec![]), Recorder::default());
+        let keys = LoopOptions { reset: "ctrl+a".parse().unwrap(), ..opts };
+        assert!(c.reset_target(&keys.reset).is_err());
+
+        /// Pauses at once, then exits after 200ms; counts the polls between.
+        struct PausedFor(std::cell::Cell<usize>, Instant);
+        impl TriggerSource for PausedFor {
+            fn recv_stamped(&self, timeout: Option<Duration>) -> Option<(TriggerEvent, Instant)> {
+                self.0.set(self.0.get() + 1);
+                if self.0.get() == 1 {
+                    return Some((TriggerEvent::Pause, Instant::now()));
+                }
+                if self.1.elapsed() > Duration::from_millis(200) {
+                    return Some((TriggerEvent::Exit, Instant::now()));
+                }
+                std::thread::sleep(timeout.unwrap_or_default());
+                None
+            }
+        }
+        let mut c = Controller::new(Engine::new(vec![Action::Char('a')], vec![]), Recorder::default());
+        c.quiet = true;
+        let paused = PausedFor(std::cell::Cell::new(0), Instant::now());
+        assert_eq!(c.run_loop(&paused, &opts).unwrap(), Flow::Exit);
+        assert!(paused.0.get() < 20, "{} polls while paused", paused.0.get());
+        assert_eq!(c.injector.out, "");
+    }
+
+    #[test]
+    fn seek_and_marker_retype_or_erase() {
+        let acts = vec![Action::Char('a'), Action::Char('b'), Action::Backspace, Action::Char('c')];
+        let engine = Engine::new(acts, vec![(2, "mid".into())]);
+        let mut c = Controller::new(engine, Recorder::default());
+        c.quiet = true;
+
+        // cueing up before Start types nothing
+        c.handle(TriggerEvent::Seek(4)).unwrap();
+        assert_eq!((c.injector.out.as_str(), c.engine.shadow.text(), c.status().pos), ("", "ac", 4));
+        c.handle(TriggerEvent::Seek(0)).unwrap();
+        c.handle(TriggerEvent::Start).unwrap();
+
+        c.handle(TriggerEvent::Seek(4)).unwrap();
+        assert_eq!(c.injector.out, "ac");
+        c.handle(TriggerEvent::GotoMarker("mid".into())).unwrap();
+        assert_eq!(c.injector.out, "ab");
+        assert_eq!(c.engine.shadow.text(), "ab");
+        assert_eq!(c.status().pos, 2);
+        assert_eq!(c.hub.latest().marker.as_deref(), Some("mid"));
+    }
+}
@@ -1,22 +1,83 @@
 use crate::{prelude::*, output::injector::OutputInjector};
-use super::playhead::Playhead;
-use crate::format::tks_json::Action;
+use super::{playhead::Playhead, shadow::ShadowBuffer};
+use crate::format::{actions::Actions, tks_json::{Action, Branch}};
+use std::collections::BTreeMap;
 
 pub struct Engine {
-    pub actions: Vec<Action>,
-    pub _markers: Vec<(usize, String)>,
+    pub actions: Actions,
+    pub markers: Vec<(usize, String)>,
+    pub notes: Vec<(usize, String)>,
+    /// Markers starting a section that [`Engine::skip`] may drop.
+    pub optional: Vec<String>,
+    pub branches: Vec<Branch>,
+    /// Output target switches, as in [`crate::format::tks_json::Sequence`].
+    pub targets: Vec<(usize, String)>,
     pub head: Playhead,
+    /// Mirror of the current output target; the others are kept in `shadows`.
+    pub shadow: ShadowBuffer,
+    target: String,
+    shadows: BTreeMap<String, ShadowBuffer>,
+    /// Target the injector was last pointed at.
+    selected: Option<String>,
+    /// Jumps taken so far, most recent last; `back` retraces them.
+    route: Vec<Jump>,
+}
+
+/// A playhead move that skips over actions: into the chosen path of a branch
+/// (`choice` is the branch and path index), or from the end of that path to
+/// the end of the branch.
+#[derive(Debug, Clone, Copy)]
+struct Jump {
+    from: usize,
+    to: usize,
+    choice: Option<(usize, usize)>,
+}
+
+/// Injector for replaying without touching the target.
+struct Silent;
+
+impl OutputInjector for Silent {
+    fn send_char(&mut self, _: char) -> Result<()> { Ok(()) }
+    fn backspace(&mut self) -> Result<()> { Ok(()) }
 }
 
 impl Engine {
-    pub fn new(actions: Vec<Action>, _markers: Vec<(usize, String)>) -> Self {
+    pub fn new(actions: impl Into<Actions>, markers: Vec<(usize, String)>) -> Self {
+        let actions = actions.into();
         let end = actions.len();
-        Self { actions, _markers, head: Playhead::new(end) }
+        Self {
+            actions,
+            markers,
+            notes: Vec::new(),
+            optional: Vec::new(),
+            branches: Vec::new(),
+            targets: Vec::new(),
+            head: Playhead::new(end),
+            shadow: ShadowBuffer::new(),
+            target: String::new(),
+            shadows: BTreeMap::new(),
+            selected: None,
+            route: Vec::new(),
+        }
     }
 
     pub fn step<I: OutputInjector>(&mut self, inj: &mut I) -> Result<()> {
         if self.head.paused || self.head.is_eof() { return Ok(()); }
-        let act = self.actions[self.head.pos];
+        self.advance(inj)
+    }
+
+    /// Steps backwards, undoing the last played action.
+    pub fn back<I: OutputInjector>(&mut self, inj: &mut I) -> Result<()> {
+        if self.head.paused || self.head.pos == 0 { return Ok(()); }
+        self.rewind(inj)
+    }
+
+    /// Plays the action under the playhead regardless of pause state; stops
+    /// at a branch until a path is chosen.
+    fn advance<I: OutputInjector>(&mut self, inj: &mut I) -> Result<()> {
+        if self.awaiting().is_some() { return Ok(()); }
+        let Some(act) = self.actions.get(self.head.pos) else { return Ok(()) };
+        self.focus(self.head.pos, inj)?;
         match act {
             Action::Char(ch) => {
                 debug!("step pos={} char={:?}", self.head.pos, ch);
@@ -27,9 +88,238 @@ impl Engine {
                 inj.backspace()?;
             }
         }
+        self.shadow.apply(act);
         self.head.pos += 1;
+        self.settle();
         Ok(())
     }
 
+    /// Output target of the action at `pos`; `""` is the default output.
+    pub fn target_at(&self, pos: usize) -> &str {
+        self.targets.iter().rev().find(|(p, _)| *p <= pos).map_or("", |(_, name)| name.as_str())
+    }
+
+    /// Text typed into each output target so far.
+    pub fn texts(&self) -> Vec<(&str, &str)> {
+        let mut texts: Vec<_> = self.shadows.iter().map(|(t, s)| (t.as_str(), s.text())).collect();
+        texts.push((&self.target, self.shadow.text()));
+        texts.sort();
+        texts
+    }
+
+    /// Makes the target of the action at `pos` current: its shadow buffer
+    /// becomes `shadow` and the injector is pointed at it.
+    fn focus<I: OutputInjector>(&mut self, pos: usize, inj: &mut I) -> Result<()> {
+        let target = self.target_at(pos);
+        if target == self.target && self.selected.as_deref() == Some(target) {
+            return Ok(());
+        }
+        let target = target.to_string();
+        if target != self.target {
+            let shadow = self.shadows.remove(&target).unwrap_or_default();
+            let old = std::mem::replace(&mut self.shadow, shadow);
+            self.shadows.insert(std::mem::replace(&mut self.target, target.clone()), old);
+        }
+        debug!("output target {:?}", target);
+        inj.target(&target)?;
+        self.selected = Some(target);
+        Ok(())
+    }
+
+    /// Forgets what was typed anywhere.
+    fn clear_targets(&mut self) {
+        self.shadow = ShadowBuffer::new();
+        self.target.clear();
+        self.shadows.clear();
+        self.selected = None;
+    }
+
+    /// Index of the branch at the playhead still waiting for a choice.
+    pub fn awaiting(&self) -> Option<usize> {
+        self.branches
+            .iter()
+            .enumerate()
+            .find(|(b, br)| br.at == self.head.pos && !self.route.iter().any(|j| j.choice.is_some_and(|c| c.0 == *b)))
+            .map(|(b, _)| b)
+    }
+
+    /// Path `which` (a name or 1-based number) of branch `b`.
+    pub fn path_index(&self, b: usize, which: &str) -> Option<usize> {
+        let paths = &self.branches[b].paths;
+        paths.iter().position(|(name, _)| name == which).or_else(|| {
+            which.parse::<usize>().ok().filter(|n| (1..=paths.len()).contains(n)).map(|n| n - 1)
+        })
+    }
+
+    /// Takes path `k` of the branch `b` the playhead is waiting at.
+    pub fn choose(&mut self, b: usize, k: usize) {
+        let to = self.branches[b].paths[k].1.start;
+        debug!("branch {} -> path {}", self.branches[b].name, self.branches[b].paths[k].0);
+        self.route.push(Jump { from: self.head.pos, to, choice: Some((b, k)) });
+        self.head.pos = to;
+        self.settle();
+    }
+
+    /// Leaves a finished path for the end of its branch (and of any branch
+    /// ending there with it).
+    fn settle(&mut self) {
+        while let Some(end) = self.route.iter().rev().find_map(|j| {
+            let (b, k) = j.choice?;
+            let br = &self.branches[b];
+            (br.paths[k].1.end == self.head.pos && br.end() != self.head.pos).then(|| br.end())
+        }) {
+            self.route.push(Jump { from: self.head.pos, to: end, choice: None });
+            self.head.pos = end;
+        }
+    }
+
+    /// Up to `n` actions from the playhead on, following the chosen path of
+    /// each branch and the default path of those not chosen yet.
+    pub fn upcoming(&self, n: usize) -> Vec<Action> {
+        let chosen = |b: usize| self.route.iter().rev().find_map(|j| j.choice.filter(|c| c.0 == b)).map_or(0, |c| c.1);
+        let mut out = Vec::with_capacity(n);
+        let mut pos = self.head.pos;
+        while out.len() < n {
+            while let Some(end) = self.branches.iter().enumerate().find_map(|(b, br)| {
+                (br.paths.get(chosen(b))?.1.end == pos && br.end() != pos).then(|| br.end())
+            }) {
+                pos = end;
+            }
+            let Some(act) = self.actions.get(pos) else { break };
+            out.push(act);
+            pos += 1;
+        }
+        out
+    }
+
+    /// Path taken at each branch passed so far, in order.
+    pub fn route(&self) -> Vec<usize> {
+        self.route.iter().filter_map(|j| j.choice.map(|(_, k)| k)).collect()
+    }
+
+    /// Undoes the action just before the playhead, retracing any jump that
+    /// led here. At the start of a chosen path it only goes back to the
+    /// branch, which then waits for a choice again.
+    fn rewind<I: OutputInjector>(&mut self, inj: &mut I) -> Result<()> {
+        while let Some(j) = self.route.last().copied().filter(|j| j.to == self.head.pos) {
+            self.head.pos = j.from;
+            self.route.pop();
+            if j.choice.is_some() { return Ok(()); }
+        }
+        let Some(act) = self.head.pos.checked_sub(1).and_then(|i| self.actions.get(i)) else { return Ok(()) };
+        self.focus(self.head.pos - 1, inj)?;
+        match self.shadow.undo(act) {
+            Some(ch) => inj.send_char(ch)?,
+            None if matches!(act, Action::Char(_)) => inj.backspace()?,
+            None => {}
+        }
+        self.head.pos -= 1;
+        Ok(())
+    }
+
+    /// Moves the playhead to `target`, typing or erasing in the target window
+    /// so it matches the script at that position. Going forward it stops at
+    /// a branch waiting for a choice; targets off the chosen route are not
+    /// reached.
+    pub fn seek<I: OutputInjector>(&mut self, target: usize, inj: &mut I) -> Result<()> {
+        let target = target.min(self.head.end);
+        debug!("seek {} -> {}", self.head.pos, target);
+        while self.head.pos < target {
+            let before = self.head.pos;
+            self.advance(inj)?;
+            if self.head.pos == before { return Ok(()); }
+        }
+        while self.head.pos > target { self.rewind(inj)?; }
+        Ok(())
+    }
+
+    /// Moves the playhead to `pos` without injecting anything, for when the
+    /// target already holds the text (e.g. resuming after a crash). `route`
+    /// is the path taken at each branch on the way, as from [`Engine::route`].
+    pub fn restore(&mut self, pos: usize, route: &[usize]) {
+        let pos = pos.min(self.head.end);
+        self.clear_targets();
+        self.head.pos = 0;
+        self.route.clear();
+        let mut choices = route.iter();
+        while self.head.pos < pos {
+            match self.awaiting() {
+                Some(b) => match choices.next().filter(|k| **k < self.branches[b].paths.len()) {
+                    Some(k) => self.choose(b, *k),
+                    None => break,
+                },
+                None => { let _ = self.advance(&mut Silent); }
+            }
+        }
+        self.selected = None;
+    }
+
+    /// Jumps to the end of the optional section under the playhead, applying
+    /// its net effect in one edit per output target: what it erases is
+    /// erased, then the rest is pasted. Branches inside take their default
+    /// path. Returns the section name, or `None` outside an optional section.
+    pub fn skip<I: OutputInjector>(&mut self, inj: &mut I) -> Result<Option<String>> {
+        let Some(name) = self.current_marker().filter(|n| self.optional.iter().any(|o| o == n)).map(str::to_string) else {
+            return Ok(None);
+        };
+        let start = self.marker_pos(&name).unwrap_or(self.head.pos);
+        let end = self.markers.iter().map(|(p, _)| *p).find(|p| *p > start).unwrap_or(self.head.end);
+        let before: BTreeMap<String, String> = self.texts().into_iter().map(|(t, s)| (t.to_string(), s.to_string())).collect();
+        let mut selected = self.selected.take();
+        while self.head.pos < end && !self.head.is_eof() {
+            match self.awaiting() {
+                Some(b) => self.choose(b, 0),
+                None => self.advance(&mut Silent)?,
+            }
+        }
+        for (target, after) in self.texts() {
+            let before = before.get(target).map_or("", String::as_str);
+            if before == after {
+                continue;
+            }
+            let common = before.chars().zip(after.chars()).take_while(|(a, b)| a == b).count();
+            debug!("skip {} in {:?}: erase {}, paste {}", name, target, before.chars().count() - common, after.chars().count() - common);
+            if selected.as_deref() != Some(target) {
+                inj.target(target)?;
+            }
+            for _ in common..before.chars().count() {
+                inj.backspace()?;
+            }
+            let rest: String = after.chars().skip(common).collect();
+            if !rest.is_empty() {
+                inj.paste(&rest)?;
+            }
+            selected = Some(target.to_string());
+        }
+        self.selected = selected;
+        Ok(Some(name))
+    }
+
+    /// Back to the start of the script, for a target that was reset.
+    pub fn reset(&mut self) {
+        self.head.pos = 0;
+        self.clear_targets();
+        self.route.clear();
+    }
+
+    pub fn marker_pos(&self, name: &str) -> Option<usize> {
+        self.markers.iter().find(|(_, n)| n == name).map(|(pos, _)| *pos)
+    }
+
+    /// Name of the last marker at or before the playhead.
+    pub fn current_marker(&self) -> Option<&str> {
+        self.markers.iter().rev().find(|(pos, _)| *pos <= self.head.pos).map(|(_, n)| n.as_str())
+    }
+
+    /// Index into `notes` of the note currently on screen.
+    pub fn current_note(&self) -> Option<usize> {
+        self.notes.iter().rposition(|(pos, _)| *pos <= self.head.pos)
+    }
+
+    pub fn next_note(&self) -> Option<usize> {
+        self.notes.iter().position(|(pos, _)| *pos > self.head.pos)
+    }
+
     pub fn toggle_pause(&mut self) { self.head.paused = !self.head.paused; }
+    pub fn set_paused(&mut self, paused: bool) { self.head.paused = paused; }
 }
@@ -0,0 +1,189 @@
+use crate::format::tks_json::{Action, Sequence};
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SAVE_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedState {
+    pub script_hash: String,
+    pub pos: usize,
+    /// Path taken at each branch before `pos`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route: Vec<usize>,
+    pub saved_at: u64,
+}
+
+/// Playhead position persisted next to the script so a crashed or interrupted
+/// talk can pick up where it left off (`--resume`). Every move is written;
+/// only syncing to disk is throttled.
+pub struct StateFile {
+    path: PathBuf,
+    script_hash: String,
+    saved: Option<(usize, Vec<usize>)>,
+    last_sync: Option<Instant>,
+}
+
+impl StateFile {
+    /// `script_hash` identifies the script (see [`sequence_hash`]), so a state
+    /// saved for another version of it is never resumed.
+    pub fn new(path: PathBuf, script_hash: String) -> Self {
+        Self { path, script_hash, saved: None, last_sync: None }
+    }
+
+    /// `demo.tks.json` -> `demo.tks.json.state`
+    pub fn default_path(script: &Path) -> PathBuf {
+        let mut name = script.as_os_str().to_owned();
+        name.push(".state");
+        PathBuf::from(name)
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Position and branch route to resume from; fails if the script changed
+    /// since it was saved.
+    pub fn load(&self) -> Result<(usize, Vec<usize>)> {
+        let bytes = std::fs::read(&self.path)
+            .map_err(|e| anyhow::anyhow!("no saved state at {}: {e}", self.path.display()))?;
+        let saved: SavedState = serde_json::from_slice(&bytes)?;
+        if saved.script_hash != self.script_hash {
+            anyhow::bail!(
+                "script changed since {} was saved; refusing to resume",
+                self.path.display()
+            );
+        }
+        Ok((saved.pos, saved.route))
+    }
+
+    /// Saves `pos` if it changed, syncing to disk at most every
+    /// `SAVE_INTERVAL`.
+    pub fn maybe_save(&mut self, pos: usize, route: &[usize]) -> Result<()> {
+        if self.saved.as_ref().map_or(true, |(p, r)| (*p, r.as_slice()) != (pos, route)) {
+            let sync = self.last_sync.map_or(true, |t| t.elapsed() >= SAVE_INTERVAL);
+            self.write(pos, route, sync)?;
+        }
+        Ok(())
+    }
+
+    /// Saves and syncs `pos`.
+    pub fn save(&mut self, pos: usize, route: &[usize]) -> Result<()> {
+        self.write(pos, route, true)
+    }
+
+    fn write(&mut self, pos: usize, route: &[usize], sync: bool) -> Result<()> {
+        use std::io::Write;
+        let saved_at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let state = SavedState { script_hash: self.script_hash.clone(), pos, route: route.to_vec(), saved_at };
+        // write + rename so a crash mid-write never leaves a torn file behind
+        let tmp = self.path.with_extension("state.tmp");
+        let mut file = std::fs::File::create(&tmp)?;
+        file.write_all(&serde_json::to_vec(&state)?)?;
+        if sync {
+            file.sync_all()?;
+            self.last_sync = Some(Instant::now());
+        }
+        std::fs::rename(&tmp, &self.path)?;
+        debug!("saved playhead {} to {}", pos, self.path.display());
+        self.saved = Some((pos, route.to_vec()));
+        Ok(())
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// FNV-1a, stable across builds and platforms (unlike `DefaultHasher`).
+pub fn script_hash(bytes: &[u8]) -> String {
+    format!("{:016x}", fnv1a(FNV_OFFSET, bytes))
+}
+
+/// Identifies what a saved position means: the actions of the loaded script
+/// (includes and placeholders resolved) and the markers, branches and output
+/// targets laid over them. Notes and timing do not count.
+pub fn sequence_hash(seq: &Sequence) -> String {
+    let mut h = FNV_OFFSET;
+    let mut buf = [0u8; 4];
+    for act in seq.actions.iter() {
+        let ch = match act {
+            Action::Char(c) => c,
+            Action::Backspace => '\u{8}',
+        };
+        h = fnv1a(h, ch.encode_utf8(&mut buf).as_bytes());
+    }
+    // 0xff never occurs in UTF-8, so it separates the parts unambiguously
+    let mark = |h: &mut u64, pos: usize, name: &str| {
+        *h = fnv1a(*h, &[0xff]);
+        *h = fnv1a(*h, &(pos as u64).to_le_bytes());
+        *h = fnv1a(*h, name.as_bytes());
+    };
+    for (pos, name) in &seq.markers {
+        mark(&mut h, *pos, name);
+    }
+    for b in &seq.branches {
+        mark(&mut h, b.at, &b.name);
+        for (name, range) in &b.paths {
+            mark(&mut h, range.start, name);
+            mark(&mut h, range.end, "");
+        }
+    }
+    for (pos, name) in &seq.targets {
+        mark(&mut h, *pos, name);
+    }
+    format!("{h:016x}")
+}
+
+fn fnv1a(mut h: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        h ^= u64::from(*b);
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("monkey-typer-{}-{}", std::process::id(), name))
+    }
+
+    fn hash(json: &str, set: &[&str]) -> String {
+        let vars = crate::format::template::Vars::new(&set.iter().map(|s| s.to_string()).collect::<Vec<_>>()).unwrap();
+        sequence_hash(&Sequence::parse(Path::new("test"), json.as_bytes(), &vars).unwrap())
+    }
+
+    #[test]
+    fn resume_round_trip_and_script_change() {
+        let v1 = hash(r#"{"version":"2","tracks":[{"id":"a","sequence":[{"type":"Insert","text":"ab"}]}]}"#, &[]);
+        let path = temp_path("resume.state");
+        let mut st = StateFile::new(path.clone(), v1.clone());
+        st.save(42, &[1, 0]).unwrap();
+        assert_eq!(StateFile::new(path.clone(), v1).load().unwrap(), (42, vec![1, 0]));
+        let v2 = hash(r#"{"version":"2","tracks":[{"id":"a","sequence":[{"type":"Insert","text":"ac"}]}]}"#, &[]);
+        assert!(StateFile::new(path.clone(), v2).load().is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn hash_covers_the_resolved_script() {
+        let json = r#"{"version":"2","meta":{"vars":{"x":"a"}},"tracks":[{"id":"a","sequence":[
+            {"type":"Insert","text":"{{x}}","t":1},{"type":"Marker","name":"m","note":"say hi"}]}]}"#;
+        assert_ne!(hash(json, &[]), hash(json, &["x=b"]));
+        assert_eq!(hash(json, &[]), hash(&json.replace("say hi", "say bye").replace("\"t\":1", "\"t\":2"), &[]));
+        assert_ne!(hash(json, &[]), hash(&json.replace("\"m\"", "\"n\""), &[]));
+    }
+
+    #[test]
+    fn every_move_is_saved() {
+        let path = temp_path("throttle.state");
+        let mut st = StateFile::new(path.clone(), "s".into());
+        st.maybe_save(1, &[]).unwrap();
+        st.maybe_save(2, &[]).unwrap();
+        assert_eq!(st.load().unwrap(), (2, vec![]));
+        st.save(3, &[]).unwrap();
+        assert_eq!(st.load().unwrap(), (3, vec![]));
+        std::fs::remove_file(path).unwrap();
+    }
+}
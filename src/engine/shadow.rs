@@ -0,0 +1,61 @@
+use crate::format::tks_json::Action;
+
+/// Mirror of the text typed into the target so far.
+///
+/// Typing is append-only (inserts at the cursor, backspaces before it), so the
+/// buffer is just a string plus the characters erased by each backspace, which
+/// lets any applied action be undone.
+#[derive(Debug, Clone, Default)]
+pub struct ShadowBuffer {
+    text: String,
+    erased: Vec<Option<char>>,
+}
+
+impl ShadowBuffer {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn text(&self) -> &str { &self.text }
+
+    pub fn apply(&mut self, act: Action) {
+        match act {
+            Action::Char(ch) => self.text.push(ch),
+            Action::Backspace => {
+                let ch = self.text.pop();
+                self.erased.push(ch);
+            }
+        }
+    }
+
+    /// Reverts `act`, which must be the last applied action. Returns the
+    /// character that has to be retyped, if any.
+    pub fn undo(&mut self, act: Action) -> Option<char> {
+        match act {
+            Action::Char(_) => {
+                self.text.pop();
+                None
+            }
+            Action::Backspace => {
+                let ch = self.erased.pop().flatten()?;
+                self.text.push(ch);
+                Some(ch)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_restores_erased_chars() {
+        let mut sb = ShadowBuffer::new();
+        let acts = [Action::Char('a'), Action::Char('b'), Action::Backspace, Action::Char('c')];
+        for a in acts { sb.apply(a); }
+        assert_eq!(sb.text(), "ac");
+
+        assert_eq!(sb.undo(Action::Char('c')), None);
+        assert_eq!(sb.undo(Action::Backspace), Some('b'));
+        assert_eq!(sb.text(), "ab");
+    }
+}
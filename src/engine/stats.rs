@@ -0,0 +1,131 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Timing of one trigger that produced injected output.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub backend: &'static str,
+    /// From the trigger source producing the event to the first injected key.
+    pub queue_ms: f64,
+    /// Time spent inside the injector.
+    pub inject_ms: f64,
+}
+
+impl Sample {
+    pub fn new(backend: &'static str, queue: Duration, inject: Duration) -> Self {
+        Self {
+            backend,
+            queue_ms: queue.as_secs_f64() * 1000.0,
+            inject_ms: inject.as_secs_f64() * 1000.0,
+        }
+    }
+
+    pub fn total_ms(&self) -> f64 { self.queue_ms + self.inject_ms }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+    /// Standard deviation, i.e. how uneven the response feels.
+    pub jitter: f64,
+}
+
+impl Summary {
+    pub fn of(values: impl IntoIterator<Item = f64>) -> Option<Self> {
+        let mut v: Vec<f64> = values.into_iter().collect();
+        if v.is_empty() {
+            return None;
+        }
+        v.sort_by(f64::total_cmp);
+        let n = v.len() as f64;
+        let mean = v.iter().sum::<f64>() / n;
+        let var = v.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+        // nearest-rank percentile
+        let pct = |p: f64| v[((p / 100.0 * n).ceil() as usize).clamp(1, v.len()) - 1];
+        Some(Self {
+            count: v.len(),
+            mean,
+            p50: pct(50.0),
+            p95: pct(95.0),
+            p99: pct(99.0),
+            max: v[v.len() - 1],
+            jitter: var.sqrt(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LatencyReport {
+    pub total: Summary,
+    pub queue: Summary,
+    pub inject: Summary,
+    pub per_backend: BTreeMap<&'static str, Summary>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    samples: Vec<Sample>,
+}
+
+impl LatencyStats {
+    pub fn record(&mut self, sample: Sample) { self.samples.push(sample); }
+
+    pub fn last(&self) -> Option<&Sample> { self.samples.last() }
+
+    pub fn total(&self) -> Option<Summary> {
+        Summary::of(self.samples.iter().map(Sample::total_ms))
+    }
+
+    pub fn report(&self) -> Option<LatencyReport> {
+        let mut per_backend: BTreeMap<&'static str, Vec<f64>> = BTreeMap::new();
+        for s in &self.samples {
+            per_backend.entry(s.backend).or_default().push(s.total_ms());
+        }
+        Some(LatencyReport {
+            total: self.total()?,
+            queue: Summary::of(self.samples.iter().map(|s| s.queue_ms))?,
+            inject: Summary::of(self.samples.iter().map(|s| s.inject_ms))?,
+            per_backend: per_backend
+                .into_iter()
+                .filter_map(|(k, v)| Some((k, Summary::of(v)?)))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_nearest_rank() {
+        let s = Summary::of((1..=100).map(f64::from)).unwrap();
+        assert_eq!((s.p50, s.p95, s.p99, s.max), (50.0, 95.0, 99.0, 100.0));
+        assert_eq!(s.mean, 50.5);
+        assert!(Summary::of(std::iter::empty()).is_none());
+
+        let flat = Summary::of([2.0, 2.0, 2.0]).unwrap();
+        assert_eq!(flat.jitter, 0.0);
+    }
+
+    #[test]
+    fn report_groups_by_backend() {
+        let mut st = LatencyStats::default();
+        let ms = Duration::from_millis;
+        st.record(Sample::new("os", ms(1), ms(2)));
+        st.record(Sample::new("os", ms(1), ms(4)));
+        st.record(Sample::new("humanize", ms(0), ms(30)));
+
+        let r = st.report().unwrap();
+        assert_eq!(r.total.count, 3);
+        assert_eq!(r.per_backend["os"].max, 5.0);
+        assert_eq!(r.per_backend["humanize"].count, 1);
+        assert!(serde_json::to_string(&r).unwrap().contains("\"p95\""));
+    }
+}
@@ -0,0 +1,56 @@
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+/// Snapshot of the playback state, published after every handled trigger.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Status {
+    pub started: bool,
+    pub paused: bool,
+    pub pos: usize,
+    pub end: usize,
+    pub marker: Option<String>,
+    pub note: Option<String>,
+    pub next_note: Option<String>,
+    /// Branch waiting for a choice at the playhead, and its path names.
+    pub branch: Option<String>,
+    pub choices: Vec<String>,
+    /// Output target the next action goes to, unless it is the default.
+    pub target: Option<String>,
+}
+
+/// Fan-out of `Status` updates to any number of observers (remote clients, ...).
+#[derive(Clone, Default)]
+pub struct StatusHub {
+    inner: Arc<Mutex<HubInner>>,
+}
+
+#[derive(Default)]
+struct HubInner {
+    last: Status,
+    subs: Vec<Sender<Status>>,
+}
+
+impl StatusHub {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn latest(&self) -> Status {
+        self.inner.lock().unwrap().last.clone()
+    }
+
+    /// Subscribes to updates; the current status is delivered right away.
+    pub fn subscribe(&self) -> Receiver<Status> {
+        let (tx, rx) = unbounded();
+        let mut inner = self.inner.lock().unwrap();
+        let _ = tx.send(inner.last.clone());
+        inner.subs.push(tx);
+        rx
+    }
+
+    pub fn publish(&self, status: Status) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.last == status { return; }
+        inner.subs.retain(|tx| tx.send(status.clone()).is_ok());
+        inner.last = status;
+    }
+}
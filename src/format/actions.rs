@@ -0,0 +1,135 @@
+use super::tks_json::Action;
+
+/// Most characters kept in one text span, so finding the n-th action inside
+/// a span stays cheap even for a multi-megabyte `Insert`.
+const MAX_SPAN_CHARS: usize = 64;
+
+/// A run of actions: the characters `text[start..end]`, or `deletes`
+/// backspaces. `pos` is the index of its first action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Span {
+    pos: usize,
+    start: usize,
+    end: usize,
+    deletes: usize,
+}
+
+/// Compact action stream: inserted text is stored once as UTF-8 and actions
+/// are addressed through spans, instead of one `Action` per character.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Actions {
+    text: String,
+    spans: Vec<Span>,
+    len: usize,
+}
+
+impl Actions {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn len(&self) -> usize { self.len }
+
+    pub fn push_text(&mut self, text: &str) {
+        for ch in text.chars() {
+            self.push(Action::Char(ch));
+        }
+    }
+
+    pub fn push_delete(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        match self.spans.last_mut() {
+            Some(last) if last.start == last.end => last.deletes += n,
+            _ => self.spans.push(Span { pos: self.len, start: 0, end: 0, deletes: n }),
+        }
+        self.len += n;
+    }
+
+    pub fn push(&mut self, act: Action) {
+        let ch = match act {
+            Action::Char(ch) => ch,
+            Action::Backspace => return self.push_delete(1),
+        };
+        let at = self.text.len();
+        self.text.push(ch);
+        match self.spans.last_mut() {
+            Some(last) if last.deletes == 0 && last.end == at && self.len - last.pos < MAX_SPAN_CHARS => {
+                last.end = self.text.len();
+            }
+            _ => self.spans.push(Span { pos: self.len, start: at, end: self.text.len(), deletes: 0 }),
+        }
+        self.len += 1;
+    }
+
+    /// Drops spare capacity once loading is done.
+    pub fn shrink_to_fit(&mut self) {
+        self.text.shrink_to_fit();
+        self.spans.shrink_to_fit();
+    }
+
+    pub fn get(&self, i: usize) -> Option<Action> {
+        self.iter_from(i).next()
+    }
+
+    /// Actions from index `i` to the end.
+    pub fn iter_from(&self, i: usize) -> impl Iterator<Item = Action> + '_ {
+        let first = self.spans.partition_point(|s| s.pos <= i).saturating_sub(1);
+        let spans = if i < self.len { &self.spans[first..] } else { &[] };
+        spans.iter().flat_map(move |s| {
+            let chars = self.text[s.start..s.end].chars().map(Action::Char);
+            let deletes = std::iter::repeat(Action::Backspace).take(s.deletes);
+            chars.chain(deletes).skip(i.saturating_sub(s.pos))
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Action> + '_ { self.iter_from(0) }
+
+    /// Bytes held on the heap, for comparing against `Vec<Action>`.
+    #[cfg(test)]
+    pub fn heap_bytes(&self) -> usize {
+        self.text.capacity() + self.spans.capacity() * std::mem::size_of::<Span>()
+    }
+}
+
+impl FromIterator<Action> for Actions {
+    fn from_iter<T: IntoIterator<Item = Action>>(iter: T) -> Self {
+        let mut out = Self::new();
+        for act in iter {
+            out.push(act);
+        }
+        out
+    }
+}
+
+impl From<Vec<Action>> for Actions {
+    fn from(v: Vec<Action>) -> Self { v.into_iter().collect() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_index_like_a_vec() {
+        let long: String = "héllo wörld ".repeat(20);
+        let mut expected: Vec<Action> = long.chars().map(Action::Char).collect();
+        expected.extend([Action::Backspace; 3]);
+        expected.push(Action::Char('!'));
+
+        let mut acts = Actions::new();
+        acts.push_text(&long);
+        acts.push_delete(2);
+        acts.push(Action::Backspace);
+        acts.push_text("!");
+
+        assert_eq!(acts.len(), expected.len());
+        assert_eq!(acts.iter().collect::<Vec<_>>(), expected);
+        for (i, act) in expected.iter().enumerate() {
+            assert_eq!(acts.get(i), Some(*act), "index {i}");
+            assert_eq!(acts.iter_from(i).count(), expected.len() - i);
+        }
+        assert_eq!(acts.get(expected.len()), None);
+        assert_eq!(Actions::from(expected.clone()), acts);
+        assert!(acts.heap_bytes() < expected.len() * std::mem::size_of::<Action>());
+    }
+}
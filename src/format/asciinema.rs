@@ -0,0 +1,239 @@
+use super::migrate;
+use super::tks_json::{Action, Sequence, TksEvent, TksFile, TksTrack};
+use crate::prelude::*;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::BufRead;
+
+#[derive(Debug, Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// Converts an asciinema v2 recording into a script. `t` is the recording
+/// time in milliseconds.
+///
+/// Keystrokes come from the input (`"i"`) events when the recording has
+/// them (`asciinema rec --stdin`). Otherwise typing is reconstructed from
+/// the output: the terminal echoes each typed key as its own one-character
+/// event, while program output and prompts arrive in larger chunks.
+/// Recording markers (`"m"`) become `Marker` events.
+pub fn import<R: BufRead>(reader: R) -> Result<TksFile> {
+    let mut lines = reader.lines().enumerate();
+    let header = match lines.next() {
+        Some((_, line)) => line?,
+        None => anyhow::bail!("empty recording"),
+    };
+    let header: Header =
+        serde_json::from_str(&header).map_err(|e| anyhow::anyhow!("line 1: bad asciinema header: {e}"))?;
+    if header.version != 2 {
+        anyhow::bail!("asciinema v{} recordings are not supported (expected v2)", header.version);
+    }
+
+    let mut events: Vec<(f64, String, String)> = Vec::new();
+    for (i, line) in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let ev = serde_json::from_str(&line).map_err(|e| anyhow::anyhow!("line {}: {e}", i + 1))?;
+        events.push(ev);
+    }
+    let from_input = events.iter().any(|(_, kind, _)| kind == "i");
+    debug!("importing {} events, keys from {}", events.len(), if from_input { "input" } else { "output" });
+
+    let mut typist = Typist::default();
+    let mut sequence = Vec::new();
+    let mut markers = HashSet::new();
+    for (time, kind, data) in &events {
+        let t = (time.max(0.0) * 1000.0).round() as u64;
+        let actions = match kind.as_str() {
+            "i" if from_input => typist.keys(data),
+            "o" if !from_input => typist.echo(data),
+            "m" => {
+                let base = if data.trim().is_empty() { format!("marker-{}", markers.len() + 1) } else { data.clone() };
+                let mut name = base.clone();
+                let mut n = 1;
+                while !markers.insert(name.clone()) {
+                    n += 1;
+                    name = format!("{base}-{n}");
+                }
+                sequence.push(TksEvent::Marker { name, note: None, optional: false, t: Some(t) });
+                continue;
+            }
+            _ => continue,
+        };
+        let chunk = Sequence { actions: actions.into_iter().collect(), ..Default::default() };
+        sequence.extend(chunk.unflatten()?.into_iter().map(|ev| ev.with_t(Some(t))));
+    }
+
+    Ok(TksFile {
+        version: migrate::CURRENT.to_string(),
+        meta: None,
+        tracks: vec![TksTrack { id: "main".to_string(), sequence }],
+    })
+}
+
+/// Tracks what has been typed so far, so line and word kills can be turned
+/// into the right number of backspaces and nothing deletes past the start.
+#[derive(Default)]
+struct Typist {
+    typed: String,
+}
+
+impl Typist {
+    /// Keys read from the terminal's input.
+    fn keys(&mut self, data: &str) -> Vec<Action> {
+        let mut out = Vec::new();
+        for ch in strip_escapes(data).chars() {
+            match ch {
+                '\r' | '\n' => self.insert('\n', &mut out),
+                '\u{7f}' | '\u{8}' => self.erase(1, &mut out),
+                // ctrl+u: kill the line
+                '\u{15}' => self.erase(self.line().chars().count(), &mut out),
+                // ctrl+w: kill the previous word
+                '\u{17}' => {
+                    let line = self.line();
+                    let word = line.trim_end().rsplit(' ').next().unwrap_or("");
+                    let spaces = line.len() - line.trim_end().len();
+                    self.erase(word.chars().count() + spaces, &mut out);
+                }
+                '\t' => self.insert('\t', &mut out),
+                c if c.is_control() => {}
+                c => self.insert(c, &mut out),
+            }
+        }
+        out
+    }
+
+    /// Output that looks like the echo of a single key.
+    fn echo(&mut self, data: &str) -> Vec<Action> {
+        let visible = strip_escapes(data);
+        let mut out = Vec::new();
+        match visible.as_str() {
+            "\u{8} \u{8}" | "\u{8}" => self.erase(1, &mut out),
+            "\r\n" | "\n" | "\r" if !self.line().is_empty() => self.insert('\n', &mut out),
+            s if s.chars().count() == 1 && !s.chars().all(char::is_control) => {
+                self.insert(s.chars().next().unwrap_or_default(), &mut out)
+            }
+            _ => {}
+        }
+        out
+    }
+
+    fn line(&self) -> &str {
+        self.typed.rsplit('\n').next().unwrap_or("")
+    }
+
+    fn insert(&mut self, ch: char, out: &mut Vec<Action>) {
+        self.typed.push(ch);
+        out.push(Action::Char(ch));
+    }
+
+    fn erase(&mut self, n: usize, out: &mut Vec<Action>) {
+        for _ in 0..n {
+            if self.typed.pop().is_none() {
+                break;
+            }
+            out.push(Action::Backspace);
+        }
+    }
+}
+
+/// Removes ANSI escape sequences (CSI, OSC, SS3 and two-byte escapes).
+fn strip_escapes(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters, then a final byte in @..~
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: up to BEL or ST
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\u{7}' || (c == '\u{1b}' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            Some('O') => {
+                chars.next();
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cast(events: &[&str]) -> String {
+        let mut s = String::from("{\"version\": 2, \"width\": 80, \"height\": 24}\n");
+        for ev in events {
+            s += ev;
+            s += "\n";
+        }
+        s
+    }
+
+    #[test]
+    fn imports_input_events_with_times_and_markers() {
+        let rec = cast(&[
+            r#"[0.5, "o", "$ "]"#,
+            r#"[1.0, "i", "ls"]"#,
+            r#"[1.2, "i", "x"]"#,
+            r#"[1.3, "i", "\u007f"]"#,
+            r#"[1.4, "i", "\u001b[A"]"#,
+            r#"[1.5, "m", "listed"]"#,
+            r#"[2.0, "i", "\r"]"#,
+            r#"[2.5, "i", "git sta\u0017"]"#,
+        ]);
+        let file = import(rec.as_bytes()).unwrap();
+        assert_eq!(
+            file.tracks[0].sequence,
+            vec![
+                TksEvent::Insert { text: "ls".into(), t: Some(1000) },
+                TksEvent::Insert { text: "x".into(), t: Some(1200) },
+                TksEvent::Delete { n: 1, t: Some(1300) },
+                TksEvent::Marker { name: "listed".into(), note: None, optional: false, t: Some(1500) },
+                TksEvent::Insert { text: "\n".into(), t: Some(2000) },
+                TksEvent::Insert { text: "git sta".into(), t: Some(2500) },
+                TksEvent::Delete { n: 3, t: Some(2500) },
+            ]
+        );
+        let text = file.to_pretty();
+        assert!(crate::format::validate::validate(text.as_bytes()).is_ok(), "{text}");
+    }
+
+    #[test]
+    fn reconstructs_typing_from_echoed_output() {
+        let rec = cast(&[
+            r#"[0.1, "o", "\u001b]0;title\u0007\u001b[32m$ \u001b[0m"]"#,
+            r#"[0.4, "o", "e"]"#,
+            r#"[0.5, "o", "c"]"#,
+            r#"[0.6, "o", "\b \b"]"#,
+            r#"[0.7, "o", "x"]"#,
+            r#"[0.9, "o", "\r\n"]"#,
+            r#"[1.0, "o", "total 0\r\ndrwxr-xr-x  2 me me 40 .\r\n"]"#,
+            r#"[1.1, "o", "\r\n"]"#,
+        ]);
+        let seq = Sequence::flatten(&import(rec.as_bytes()).unwrap());
+        let mut typed = crate::engine::shadow::ShadowBuffer::new();
+        seq.actions.iter().for_each(|a| typed.apply(a));
+        assert_eq!(typed.text(), "ex\n");
+
+        assert!(import(&b"{\"version\": 1}\n"[..]).is_err());
+    }
+}
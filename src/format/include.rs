@@ -0,0 +1,150 @@
+use super::template::Vars;
+use super::tks_json::{TksEvent, TksFile, TksPath};
+use super::validate;
+use std::path::{Path, PathBuf};
+
+/// Resolves `Include` events at load time. Paths are relative to the file
+/// doing the including; the chain of open files catches cycles.
+#[derive(Debug, Clone)]
+pub struct Includes {
+    stack: Vec<PathBuf>,
+}
+
+impl Includes {
+    /// Resolver for includes of `script`.
+    pub fn new(script: &Path) -> Self {
+        Self { stack: vec![script.canonicalize().unwrap_or_else(|_| script.to_path_buf())] }
+    }
+
+    /// The events `ev` stands for: an `Include` becomes the included track
+    /// (its markers prefixed with `as/`, or the file stem) or the raw text of
+    /// the file; the paths of a `Branch` are expanded in place; anything else
+    /// is returned as is. Included events take the time of the `Include`.
+    ///
+    /// `ev` must already be rendered. Events of included scripts are rendered
+    /// with `vars` before they are expanded; raw text is typed verbatim.
+    pub fn expand(&mut self, ev: &TksEvent, vars: &Vars) -> Result<Vec<TksEvent>, String> {
+        if let TksEvent::Branch { name, paths, t } = ev {
+            let mut expanded = Vec::with_capacity(paths.len());
+            for path in paths {
+                let mut sequence = Vec::new();
+                for inner in &path.sequence {
+                    sequence.extend(self.expand(inner, vars)?);
+                }
+                expanded.push(TksPath { name: path.name.clone(), sequence });
+            }
+            return Ok(vec![TksEvent::Branch { name: name.clone(), paths: expanded, t: *t }]);
+        }
+        let TksEvent::Include { file, track, ns, t } = ev else { return Ok(vec![ev.clone()]) };
+        let dir = self.stack.last().and_then(|p| p.parent()).unwrap_or(Path::new("."));
+        let path = dir.join(file);
+        let canonical = path.canonicalize().map_err(|e| format!("cannot include {file}: {e}"))?;
+        if let Some(i) = self.stack.iter().position(|p| *p == canonical) {
+            let chain: Vec<String> = self.stack[i..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.file_name().map_or_else(|| p.display().to_string(), |n| n.to_string_lossy().into_owned()))
+                .collect();
+            return Err(format!("include cycle: {}", chain.join(" -> ")));
+        }
+
+        if !file.ends_with(".tks.json") {
+            if track.is_some() || ns.is_some() {
+                return Err(format!("{file} is raw text; `track` and `as` only apply to .tks.json includes"));
+            }
+            let text = std::fs::read_to_string(&path).map_err(|e| format!("cannot include {file}: {e}"))?;
+            return Ok(vec![TksEvent::Insert { text, t: *t }]);
+        }
+
+        let bytes = std::fs::read(&path).map_err(|e| format!("cannot include {file}: {e}"))?;
+        let included: TksFile = validate::validate(&bytes).map_err(|diags| {
+            let d = &diags[0];
+            format!("{file}:{}:{}: {}", d.line, d.column, d.message)
+        })?;
+        let events = match track {
+            Some(id) => included.tracks.iter().find(|tr| tr.id == *id).ok_or_else(|| format!("{file} has no track {id:?}"))?,
+            None => &included.tracks[0],
+        };
+        let prefix = ns.clone().unwrap_or_else(|| file.rsplit('/').next().unwrap_or(file).trim_end_matches(".tks.json").to_string());
+
+        self.stack.push(canonical);
+        let mut out = Vec::new();
+        let mut result = Ok(());
+        for inner in &events.sequence {
+            let rendered = vars.render_event(inner.clone()).map_err(|p| format!("{file}: {p}"));
+            match rendered.and_then(|inner| self.expand(&inner, vars)) {
+                Ok(evs) => out.extend(evs.into_iter().map(|e| namespaced(e, &prefix, *t))),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.stack.pop();
+        result.map(|_| out)
+    }
+}
+
+/// An included event: markers get `prefix/`, times become `t`.
+fn namespaced(ev: TksEvent, prefix: &str, t: Option<u64>) -> TksEvent {
+    match ev {
+        TksEvent::Marker { name, note, optional, .. } => TksEvent::Marker { name: format!("{prefix}/{name}"), note, optional, t },
+        TksEvent::Branch { name, paths, .. } => TksEvent::Branch {
+            name,
+            paths: paths
+                .into_iter()
+                .map(|p| TksPath { name: p.name, sequence: p.sequence.into_iter().map(|e| namespaced(e, prefix, t)).collect() })
+                .collect(),
+            t,
+        },
+        e => e.with_t(t),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{template::Vars, tks_json::Sequence};
+
+    #[test]
+    fn includes_tracks_and_text_and_rejects_cycles() {
+        let dir = std::env::temp_dir().join(format!("monkey-typer-include-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        let script = |events: &str| format!(r#"{{"version":"2","tracks":[{{"id":"main","sequence":[{events}]}}]}}"#);
+        std::fs::write(dir.join("lib/Cargo.toml"), "[package]\n").unwrap();
+        std::fs::write(
+            dir.join("lib/skeleton.tks.json"),
+            script(r#"{"type":"Marker","name":"top"},{"type":"Include","file":"Cargo.toml"},{"type":"Insert","text":"fn main() {}"}"#),
+        )
+        .unwrap();
+        let main = dir.join("talk.tks.json");
+        let body = script(r#"{"type":"Include","file":"lib/skeleton.tks.json","t":5},{"type":"Delete","n":2},{"type":"Marker","name":"top"}"#);
+        std::fs::write(&main, &body).unwrap();
+
+        let none = Vars::default();
+        let seq = Sequence::parse(&main, body.as_bytes(), &none).unwrap();
+        assert_eq!(seq.final_text(), "[package]\nfn main() ");
+        assert_eq!(seq.markers, vec![(0, "skeleton/top".to_string()), (24, "top".to_string())]);
+        assert_eq!(Sequence::read(body.as_bytes(), &main, &none).unwrap(), seq);
+
+        // included raw text is typed verbatim, placeholders and all
+        std::fs::write(dir.join("lib/main.rs"), "println!(\"{{}}\", 1);").unwrap();
+        let raw = script(r#"{"type":"Insert","text":"{{PATH}}"},{"type":"Include","file":"lib/main.rs"}"#);
+        let seq = Sequence::parse(&main, raw.as_bytes(), &none).unwrap();
+        assert_eq!(seq.final_text(), std::env::var("PATH").unwrap() + "println!(\"{{}}\", 1);");
+        assert_eq!(Sequence::read(raw.as_bytes(), &main, &none).unwrap(), seq);
+
+        // the Delete is only checked against included text once it is resolved
+        let short = script(r#"{"type":"Include","file":"lib/Cargo.toml"},{"type":"Delete","n":20}"#);
+        assert!(validate::validate(short.as_bytes()).is_ok());
+        let err = Sequence::parse(&main, short.as_bytes(), &none).unwrap_err().to_string();
+        assert!(err.contains("Delete of 20 exceeds the 10 characters"), "{err}");
+
+        std::fs::write(dir.join("lib/a.tks.json"), script(r#"{"type":"Include","file":"b.tks.json"}"#)).unwrap();
+        std::fs::write(dir.join("lib/b.tks.json"), script(r#"{"type":"Include","file":"a.tks.json"}"#)).unwrap();
+        let cyclic = script(r#"{"type":"Include","file":"lib/a.tks.json"}"#);
+        let err = Sequence::parse(&main, cyclic.as_bytes(), &none).unwrap_err().to_string();
+        assert!(err.contains("include cycle: a.tks.json -> b.tks.json -> a.tks.json"), "{err}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,117 @@
+use serde_json::Value;
+
+/// Upgrade steps in order: `STEPS[i]` turns a version `i + 1` document into
+/// version `i + 2`. Adding a format change means appending a step here.
+const STEPS: &[fn(&mut Value)] = &[v1_to_v2];
+
+/// The script format version this build reads and writes.
+pub const CURRENT: u32 = STEPS.len() as u32 + 1;
+
+/// Brings `doc` up to `CURRENT` in place and returns the version it had.
+pub fn upgrade(doc: &mut Value) -> Result<u32, String> {
+    let from = version_of(doc.get("version").ok_or("missing field `version`")?)?;
+    for step in &STEPS[from as usize - 1..] {
+        step(doc);
+    }
+    doc["version"] = Value::String(CURRENT.to_string());
+    Ok(from)
+}
+
+/// Parses a `version` value, rejecting versions newer than `CURRENT`.
+pub fn version_of(v: &Value) -> Result<u32, String> {
+    let version = match v {
+        Value::String(s) => s.trim().parse::<u32>().ok(),
+        Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+        _ => None,
+    };
+    match version {
+        Some(v) if v > CURRENT => Err(format!(
+            "version {v} is newer than this build supports ({CURRENT}); upgrade monkey-typer"
+        )),
+        Some(v) if v >= 1 => Ok(v),
+        _ => Err(format!("unknown version {v}")),
+    }
+}
+
+/// v2: `Insert` text may hold `{{name}}` placeholders. Version 1 typed it
+/// verbatim, so every `{{` is escaped to keep the text as it was.
+fn v1_to_v2(doc: &mut Value) {
+    for_each_event(doc, &mut |ev| {
+        if ev.get("type").and_then(Value::as_str) == Some("Insert") {
+            if let Some(Value::String(text)) = ev.get_mut("text") {
+                *text = escape_placeholders(text);
+            }
+        }
+    });
+}
+
+/// `text` with every `{{` escaped, so it renders back to itself: the
+/// backslashes before it are doubled and one more is added.
+fn escape_placeholders(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find("{{") {
+        let before = rest[..i].trim_end_matches('\\');
+        out += before;
+        out += &"\\".repeat(2 * (i - before.len()) + 1);
+        out += "{{";
+        rest = &rest[i + 2..];
+    }
+    out + rest
+}
+
+/// Calls `f` on every event of every track, including those inside
+/// `Branch` paths.
+fn for_each_event(doc: &mut Value, f: &mut impl FnMut(&mut Value)) {
+    fn walk(events: &mut Value, f: &mut impl FnMut(&mut Value)) {
+        for ev in events.as_array_mut().into_iter().flatten() {
+            for path in ev.get_mut("paths").and_then(Value::as_array_mut).into_iter().flatten() {
+                if let Some(sequence) = path.get_mut("sequence") {
+                    walk(sequence, f);
+                }
+            }
+            f(ev);
+        }
+    }
+    for track in doc.get_mut("tracks").and_then(Value::as_array_mut).into_iter().flatten() {
+        if let Some(sequence) = track.get_mut("sequence") {
+            walk(sequence, f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn upgrades_v1_and_rejects_newer() {
+        let mut doc = json!({"version":"1","tracks":[{"id":"main","sequence":[
+            {"type":"Insert","text":"println!(\"{{}}\", x);","t":3},
+            {"type":"Branch","name":"b","paths":[{"name":"p","sequence":[{"type":"Insert","text":"{{a}}"}]}]}
+        ]}]});
+        assert_eq!(upgrade(&mut doc), Ok(1));
+        assert_eq!(doc["version"], "2");
+        let events = &doc["tracks"][0]["sequence"];
+        assert_eq!(events[0].to_string(), r#"{"type":"Insert","text":"println!(\"\\{{}}\", x);","t":3}"#);
+        assert_eq!(events[1]["paths"][0]["sequence"][0]["text"], "\\{{a}}");
+
+        // already current: untouched
+        let before = doc.clone();
+        assert_eq!(upgrade(&mut doc), Ok(CURRENT));
+        assert_eq!(doc, before);
+
+        let err = upgrade(&mut json!({"version":"99","tracks":[]})).unwrap_err();
+        assert!(err.contains("newer"), "{err}");
+        assert!(upgrade(&mut json!({"version":"v1","tracks":[]})).is_err());
+    }
+
+    #[test]
+    fn escaped_text_renders_back_to_itself() {
+        let vars = crate::format::template::Vars::default();
+        for text in ["{{x}}", r"\{{x}}", r"\\{{", "{{{", r"a\b {{ }} \", "plain"] {
+            assert_eq!(vars.render(&escape_placeholders(text)).as_deref(), Ok(text), "{text}");
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use super::include::Includes;
+use super::migrate;
+use super::template::Vars;
+use super::tks_json::{Sequence, TksEvent, TksMeta};
+use super::validate::Checker;
+use crate::prelude::*;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::fmt;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Scripts up to this size that cannot stream by design (an older format
+/// version, or `meta` after `tracks` that placeholders need) are loaded
+/// whole instead. Larger ones report the streaming error.
+const FULL_PARSE_LIMIT: u64 = 16 << 20;
+
+impl Sequence {
+    /// Loads a script from disk without holding the document in memory:
+    /// events are checked and flattened into compact spans as they are read.
+    ///
+    /// Only current-version scripts with `meta` before `tracks` stream. Other
+    /// scripts up to `FULL_PARSE_LIMIT` (16 MiB) are loaded whole with a
+    /// warning; `monkey-typer migrate` rewrites them in streamable form. Any
+    /// other problem is reported as streaming found it.
+    pub fn load(path: &Path, vars: &Vars) -> Result<Self> {
+        let name = path.display().to_string();
+        let file = std::fs::File::open(path).map_err(|e| anyhow::anyhow!("{name}: {e}"))?;
+        match Builder::new(path, vars).read(BufReader::new(file)) {
+            Ok(seq) => Ok(seq),
+            Err((e, true)) if std::fs::metadata(path)?.len() <= FULL_PARSE_LIMIT => {
+                warn!("cannot stream {} ({}); loading it whole", name, e);
+                Self::parse(path, &std::fs::read(path)?, vars)
+            }
+            Err((e, _)) => Err(anyhow::anyhow!("{name}: {e}")),
+        }
+    }
+
+    /// Streams a current-version script, running the same checks as
+    /// `validate` and flattening the first track on the fly. Includes are
+    /// resolved relative to `script`; placeholders need `meta` to come
+    /// before `tracks`, as `migrate` writes it, and fail as undefined
+    /// otherwise.
+    #[cfg(test)]
+    pub fn read<R: Read>(reader: R, script: &Path, vars: &Vars) -> std::result::Result<Self, serde_json::Error> {
+        Builder::new(script, vars).read(reader).map_err(|(e, _)| e)
+    }
+}
+
+struct Builder {
+    seq: Sequence,
+    checker: Checker,
+    includes: Includes,
+    vars: Vars,
+    meta_seen: bool,
+    /// Set when streaming fails by design: see `FULL_PARSE_LIMIT`.
+    unstreamable: bool,
+}
+
+impl Builder {
+    fn new(script: &Path, vars: &Vars) -> Self {
+        Self {
+            seq: Sequence::default(),
+            checker: Checker::default(),
+            includes: Includes::new(script),
+            vars: vars.clone(),
+            meta_seen: false,
+            unstreamable: false,
+        }
+    }
+
+    /// On error, also tells whether the full parser could load the script.
+    fn read<R: Read>(mut self, reader: R) -> std::result::Result<Sequence, (serde_json::Error, bool)> {
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        let done = FileSeed(&mut self).deserialize(&mut de).and_then(|()| de.end());
+        if let Err(e) = done {
+            return Err((e, self.unstreamable));
+        }
+        self.seq.actions.shrink_to_fit();
+        Ok(self.seq)
+    }
+}
+
+struct FileSeed<'a>(&'a mut Builder);
+
+impl<'de> DeserializeSeed<'de> for FileSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<(), D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FileSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("a .tks.json script") }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        let (mut version, mut tracks) = (false, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" => {
+                    let v: serde_json::Value = map.next_value()?;
+                    match migrate::version_of(&v).map_err(de::Error::custom)? {
+                        migrate::CURRENT => version = true,
+                        old => {
+                            self.0.unstreamable = true;
+                            return Err(de::Error::custom(format!(
+                                "version {old} must be migrated before streaming; run `monkey-typer migrate`"
+                            )))
+                        }
+                    }
+                }
+                "meta" => {
+                    let meta: TksMeta = map.next_value()?;
+                    if let Some((field, problem)) = Checker::meta(&meta).into_iter().next() {
+                        return Err(de::Error::custom(format!("meta{field}: {problem}")));
+                    }
+                    self.0.vars = self.0.vars.with_meta(&meta);
+                    self.0.meta_seen = true;
+                    self.0.seq.meta = meta;
+                }
+                "tracks" => tracks = Some(map.next_value_seed(TracksSeed(&mut *self.0))?),
+                other => return Err(de::Error::unknown_field(other, &["version", "meta", "tracks"])),
+            }
+        }
+        if !version {
+            return Err(de::Error::missing_field("version"));
+        }
+        match tracks {
+            None => return Err(de::Error::missing_field("tracks")),
+            Some(0) => return Err(de::Error::custom("no tracks")),
+            Some(_) => {}
+        }
+        match Checker::final_hash(&self.0.seq.meta, &self.0.seq) {
+            Some(problem) => Err(de::Error::custom(format!("meta/final_hash: {problem}"))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the number of tracks.
+struct TracksSeed<'a>(&'a mut Builder);
+
+impl<'de> DeserializeSeed<'de> for TracksSeed<'_> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<usize, D::Error> {
+        d.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TracksSeed<'_> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("a list of tracks") }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<usize, A::Error> {
+        let mut n = 0;
+        while seq.next_element_seed(TrackSeed { b: &mut *self.0, index: n })?.is_some() {
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+struct TrackSeed<'a> {
+    b: &'a mut Builder,
+    index: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for TrackSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<(), D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TrackSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("a track") }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> std::result::Result<(), A::Error> {
+        let (mut id, mut sequence) = (false, false);
+        self.b.checker.new_track();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "id" => {
+                    let v: String = map.next_value()?;
+                    if let Some(problem) = Checker::track_id(&v) {
+                        return Err(de::Error::custom(format!("tracks[{}]: {problem}", self.index)));
+                    }
+                    id = true;
+                }
+                "sequence" => {
+                    map.next_value_seed(EventsSeed { b: &mut *self.b, track: self.index })?;
+                    sequence = true;
+                }
+                other => return Err(de::Error::unknown_field(other, &["id", "sequence"])),
+            }
+        }
+        match (id, sequence) {
+            (false, _) => Err(de::Error::missing_field("id")),
+            (_, false) => Err(de::Error::missing_field("sequence")),
+            _ => Ok(()),
+        }
+    }
+}
+
+struct EventsSeed<'a> {
+    b: &'a mut Builder,
+    track: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for EventsSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> std::result::Result<(), D::Error> {
+        d.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for EventsSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("a list of events") }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<(), A::Error> {
+        let mut j = 0;
+        // only the first track is played; the others are still checked
+        while let Some(ev) = seq.next_element::<TksEvent>()? {
+            let at = format!("tracks[{}].sequence[{j}]", self.track);
+            // placeholders are filled in before includes are expanded, so
+            // raw included text is never rendered
+            let ev = match self.b.vars.render_event(ev) {
+                Ok(ev) => ev,
+                Err(p) if self.b.meta_seen => return Err(de::Error::custom(format!("{at}: {p}"))),
+                Err(p) => {
+                    self.b.unstreamable = true;
+                    return Err(de::Error::custom(format!("{at}: {p}; streaming needs meta before tracks")));
+                }
+            };
+            let events = self.b.includes.expand(&ev, &self.b.vars).map_err(|e| de::Error::custom(format!("{at}: {e}")))?;
+            let located = |problem: String| match &ev {
+                TksEvent::Include { file, .. } => format!("{at}: in {file}: {problem}"),
+                _ => format!("{at}: {problem}"),
+            };
+            for inner in events {
+                if let Some((_, problem)) = self.b.checker.event(&inner).into_iter().next() {
+                    return Err(de::Error::custom(located(problem)));
+                }
+                if self.track == 0 {
+                    self.b.seq.push_event(&inner);
+                }
+            }
+            j += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::tks_json::Action;
+    use std::time::Instant;
+
+    fn read(bytes: &[u8]) -> std::result::Result<Sequence, serde_json::Error> {
+        Sequence::read(bytes, Path::new("test"), &Vars::default())
+    }
+
+    fn parse(bytes: &[u8]) -> Sequence {
+        Sequence::parse(Path::new("test"), bytes, &Vars::default()).unwrap()
+    }
+
+    #[test]
+    fn streams_the_same_sequence_as_the_full_parser() {
+        let demo = include_bytes!("../../demo.tks.json");
+        assert_eq!(read(demo).unwrap(), parse(demo));
+
+        let bad = "{\"version\":\"2\",\"tracks\":[{\"id\":\"main\",\"sequence\":[\n{\"type\":\"Insert\",\"text\":\"a\"},\n{\"type\":\"Delete\",\"n\":2}]}]}";
+        let err = read(bad.as_bytes()).unwrap_err().to_string();
+        assert!(err.contains("tracks[0].sequence[1]: Delete of 2 exceeds"), "{err}");
+        assert_eq!(err.rsplit("at line ").next(), Some("3 column 24"));
+
+        let v1 = br#"{"version":"1","tracks":[{"id":"main","sequence":[]}]}"#;
+        assert!(read(&v1[..]).unwrap_err().to_string().contains("migrate"));
+        let meta = br#"{"version":"2","meta":{"title":"t","cps":30},"tracks":[{"id":"a","sequence":[{"type":"Insert","text":"x"}]}]}"#;
+        assert_eq!(read(meta).unwrap(), parse(meta));
+        let wrong = br#"{"version":"2","tracks":[{"id":"a","sequence":[]}],"meta":{"final_hash":"00"}}"#;
+        assert!(read(&wrong[..]).unwrap_err().to_string().contains("final text hash"));
+        let later_tracks = br#"{"version":"2","tracks":[{"id":"a","sequence":[{"type":"Insert","text":"x"}]},{"id":"b","sequence":[{"type":"Insert","text":"yz"}]}]}"#;
+        assert_eq!(read(&later_tracks[..]).unwrap().actions.len(), 1);
+    }
+
+    #[test]
+    fn placeholders_are_filled_before_checking() {
+        let script = |n: u32| {
+            format!(r#"{{"version":"2","meta":{{"vars":{{"name":"ab"}}}},"tracks":[{{"id":"a","sequence":[{{"type":"Insert","text":"{{{{name}}}}!"}},{{"type":"Delete","n":{n}}}]}}]}}"#)
+        };
+        assert_eq!(read(script(2).as_bytes()).unwrap().final_text(), "a");
+        assert!(read(script(4).as_bytes()).unwrap_err().to_string().contains("Delete of 4 exceeds the 3"));
+        let set = Vars::new(&["name=xyz".into()]).unwrap();
+        let seq = Sequence::read(script(1).as_bytes(), Path::new("test"), &set).unwrap();
+        assert_eq!(seq, Sequence::parse(Path::new("test"), script(1).as_bytes(), &set).unwrap());
+        assert_eq!(seq.final_text(), "xyz");
+        let undefined = br#"{"version":"2","tracks":[{"id":"a","sequence":[{"type":"Insert","text":"{{nope_xyz}}"}]}]}"#;
+        assert!(read(undefined).unwrap_err().to_string().contains("undefined variable \"nope_xyz\""));
+        let late_meta = br#"{"version":"2","tracks":[{"id":"a","sequence":[{"type":"Insert","text":"{{name}}"}]}],"meta":{"vars":{"name":"ab"}}}"#;
+        assert!(read(late_meta).unwrap_err().to_string().contains("streaming needs meta before tracks"));
+        assert_eq!(parse(late_meta).final_text(), "ab");
+    }
+
+    #[test]
+    fn only_unstreamable_scripts_fall_back_to_the_full_parser() {
+        let fallback = |bytes: &[u8]| Builder::new(Path::new("test"), &Vars::default()).read(bytes).unwrap_err().1;
+        assert!(fallback(br#"{"version":"1","tracks":[{"id":"main","sequence":[]}]}"#));
+        assert!(fallback(br#"{"version":"2","tracks":[{"id":"a","sequence":[{"type":"Insert","text":"{{name}}"}]}],"meta":{"vars":{"name":"ab"}}}"#));
+        assert!(!fallback(br#"{"version":"2","tracks":[{"id":"a","sequence":[{"type":"Insert","txet":"x"}]}]}"#));
+        assert!(!fallback(br#"{"version":"2","meta":{},"tracks":[{"id":"a","sequence":[{"type":"Insert","text":"{{nope}}"}]}]}"#));
+    }
+
+    /// A `VmRSS:`/`VmHWM:` figure from /proc (Linux only), in bytes.
+    fn proc_status(field: &str) -> usize {
+        let status = std::fs::read_to_string("/proc/self/status").unwrap();
+        let kb = status.lines().find_map(|l| l.strip_prefix(field)).unwrap();
+        kb.trim().trim_end_matches("kB").trim().parse::<usize>().unwrap() * 1024
+    }
+
+    /// Loads `BENCH_SCRIPT` with the `BENCH_LOADER` loader and prints its time
+    /// and peak memory. `bench_large_script` runs it in a fresh process so
+    /// each loader's peak is measured alone.
+    #[test]
+    #[ignore = "run by bench_large_script"]
+    fn bench_load_one() {
+        let Ok(path) = std::env::var("BENCH_SCRIPT") else { return };
+        let path = std::path::PathBuf::from(path);
+        let base = proc_status("VmRSS:");
+        let t0 = Instant::now();
+        let seq = match std::env::var("BENCH_LOADER").as_deref() {
+            Ok("full") => Sequence::parse(&path, &std::fs::read(&path).unwrap(), &Vars::default()).unwrap(),
+            _ => Sequence::load(&path, &Vars::default()).unwrap(),
+        };
+        let ms = t0.elapsed().as_secs_f64() * 1000.0;
+        println!("BENCH {ms} {} {}", proc_status("VmHWM:").saturating_sub(base), seq.actions.len());
+    }
+
+    /// Runs `bench_load_one` in a child process: time and peak memory.
+    fn measure(path: &Path, loader: &str) -> (f64, usize) {
+        let out = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "format::stream::tests::bench_load_one", "--ignored", "--nocapture"])
+            .env("BENCH_SCRIPT", path)
+            .env("BENCH_LOADER", loader)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let line = stdout.lines().find_map(|l| l.split_once("BENCH ")).expect("no BENCH line").1;
+        let mut fields = line.split(' ');
+        (fields.next().unwrap().parse().unwrap(), fields.next().unwrap().parse().unwrap())
+    }
+
+    #[test]
+    #[ignore = "benchmark: cargo test --release bench_ -- --ignored --nocapture"]
+    fn bench_large_script() {
+        let path = std::env::temp_dir().join(format!("monkey-typer-bench-{}.tks.json", std::process::id()));
+        {
+            use std::io::Write;
+            let mut f = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+            write!(f, "{{\n  \"version\": \"2\",\n  \"tracks\": [\n    {{\n      \"id\": \"main\",\n      \"sequence\": [\n").unwrap();
+            for i in 0..400_000 {
+                let line = format!("    let value_{i} = compute(&input[{i}..], {i});\\n");
+                writeln!(f, "        {{\"t\":{i},\"type\":\"Insert\",\"text\":\"{line}\"}},").unwrap();
+                if i % 10 == 0 {
+                    writeln!(f, "        {{\"t\":{i},\"type\":\"Delete\",\"n\":3}},").unwrap();
+                }
+            }
+            write!(f, "        {{\"type\":\"Marker\",\"name\":\"end\"}}\n      ]\n    }}\n  ]\n}}\n").unwrap();
+        }
+        let mb = std::fs::metadata(&path).unwrap().len() as f64 / (1 << 20) as f64;
+
+        let (full_ms, full_peak) = measure(&path, "full");
+        let (stream_ms, stream_peak) = measure(&path, "stream");
+        let streamed = Sequence::load(&path, &Vars::default()).unwrap();
+        let vec_held = streamed.actions.len() * std::mem::size_of::<Action>();
+        let spans_held = streamed.actions.heap_bytes();
+        std::fs::remove_file(&path).unwrap();
+
+        let mib = |b: usize| b as f64 / (1 << 20) as f64;
+        println!("script: {:.1} MiB, {} actions", mb, streamed.actions.len());
+        println!("full parse:  {:>8.1} ms  peak {:>7.1} MiB", full_ms, mib(full_peak));
+        println!("streaming:   {:>8.1} ms  peak {:>7.1} MiB", stream_ms, mib(stream_peak));
+        println!("Vec<Action>: {:>7.1} MiB held", mib(vec_held));
+        println!("Actions:     {:>7.1} MiB held", mib(spans_held));
+        assert!(stream_peak < full_peak);
+        assert!(spans_held < vec_held);
+    }
+}
@@ -0,0 +1,123 @@
+use super::tks_json::{TksEvent, TksMeta, TksPath};
+use crate::prelude::*;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Values for `{{name}}` placeholders in `Insert` text: `--set` first, then
+/// the script's `meta.vars`, then environment variables.
+///
+/// Backslashes only matter right before `{{`: `\{{` types a literal `{{` and
+/// `\\` a single backslash, so `\\{{name}}` types a backslash and the value.
+/// Each backslash is doubled again in JSON (`"\\{{"`).
+#[derive(Debug, Clone, Default)]
+pub struct Vars {
+    set: BTreeMap<String, String>,
+    meta: Map<String, Value>,
+}
+
+impl Vars {
+    /// Parses `key=value` assignments from the command line.
+    pub fn new(assignments: &[String]) -> Result<Self> {
+        let mut set = BTreeMap::new();
+        for a in assignments {
+            let (k, v) = a.split_once('=').ok_or_else(|| anyhow::anyhow!("--set {a:?}: expected key=value"))?;
+            if !is_name(k) {
+                anyhow::bail!("--set {a:?}: invalid variable name {k:?}");
+            }
+            set.insert(k.to_string(), v.to_string());
+        }
+        Ok(Self { set, meta: Map::new() })
+    }
+
+    /// These values backed by the script's own `meta.vars`.
+    pub fn with_meta(&self, meta: &TksMeta) -> Self {
+        Self { set: self.set.clone(), meta: meta.vars.clone() }
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        if let Some(v) = self.set.get(name) {
+            return Some(v.clone());
+        }
+        match self.meta.get(name) {
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(v) => Some(v.to_string()),
+            None => std::env::var(name).ok(),
+        }
+    }
+
+    /// `ev` with its placeholders filled in.
+    pub fn render_event(&self, ev: TksEvent) -> std::result::Result<TksEvent, String> {
+        match ev {
+            TksEvent::Insert { text, t } if text.contains("{{") => Ok(TksEvent::Insert { text: self.render(&text)?, t }),
+            TksEvent::Branch { name, paths, t } => {
+                let paths = paths
+                    .into_iter()
+                    .map(|p| {
+                        let sequence = p.sequence.into_iter().map(|ev| self.render_event(ev)).collect::<std::result::Result<_, _>>()?;
+                        Ok(TksPath { name: p.name, sequence })
+                    })
+                    .collect::<std::result::Result<_, String>>()?;
+                Ok(TksEvent::Branch { name, paths, t })
+            }
+            ev => Ok(ev),
+        }
+    }
+
+    pub fn render(&self, text: &str) -> std::result::Result<String, String> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(i) = rest.find("{{") {
+            let before = rest[..i].trim_end_matches('\\');
+            let slashes = i - before.len();
+            out += before;
+            out += &"\\".repeat(slashes / 2);
+            if slashes % 2 == 1 {
+                out += "{{";
+                rest = &rest[i + 2..];
+                continue;
+            }
+            let after = &rest[i + 2..];
+            let end = after.find("}}").ok_or_else(|| "unclosed {{ (write \\{{ for a literal {{)".to_string())?;
+            let name = after[..end].trim();
+            if !is_name(name) {
+                return Err(format!("invalid variable name {name:?}"));
+            }
+            out += &self.get(name).ok_or_else(|| format!("undefined variable {name:?} (use --set {name}=...)"))?;
+            rest = &after[end + 2..];
+        }
+        out += rest;
+        Ok(out)
+    }
+}
+
+/// Letters, digits, `_`, `-` and `.`, not starting with a digit.
+pub fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_resolve_in_precedence_order() {
+        let meta = TksMeta {
+            vars: serde_json::from_str(r#"{"project":"demo","port":8080}"#).unwrap(),
+            ..Default::default()
+        };
+        let vars = Vars::new(&["project=talk".into()]).unwrap().with_meta(&meta);
+        assert_eq!(
+            vars.render("cargo new {{project}} # {{ port }}").unwrap(),
+            "cargo new talk # 8080"
+        );
+        assert_eq!(vars.render("{{PATH}}").unwrap(), std::env::var("PATH").unwrap());
+        assert_eq!(vars.render(r#"format!("\{{}}", x)"#).unwrap(), r#"format!("{{}}", x)"#);
+        assert_eq!(vars.render(r#"C:\\{{project}} \\\{{x}} a\b"#).unwrap(), r#"C:\talk \{{x}} a\b"#);
+        assert!(vars.render("{{missing_var_xyz}}").unwrap_err().contains("undefined variable"));
+        assert!(vars.render("{{ a b }}").unwrap_err().contains("invalid variable name"));
+        assert!(vars.render("{{open").unwrap_err().contains("unclosed"));
+        assert!(Vars::new(&["novalue".into()]).is_err());
+    }
+}
@@ -1,70 +1,442 @@
+use super::{actions::Actions, template::Vars, validate};
 use crate::prelude::*;
-use serde::Deserialize;
-
-#[derive(Debug, Deserialize)]
-struct TksFile {
-    #[allow(dead_code)]
-    version: String,
-    tracks: Vec<TksTrack>,
-}
-#[derive(Debug, Deserialize)]
-struct TksTrack {
-    #[allow(dead_code)]
-    id: String,
-    sequence: Vec<TksEvent>,
-}
-#[derive(Debug, Deserialize)]
-#[serde(tag = "type")]
-enum TksEvent {
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use std::ops::Range;
+
+/// A script as written on disk. Deserializing it directly skips validation
+/// and migration; use [`TksFile::parse`] for files from users.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TksFile {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<TksMeta>,
+    pub tracks: Vec<TksTrack>,
+}
+
+/// Optional script metadata. The playback settings are defaults that
+/// explicit CLI flags override.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TksMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Language of the typed code, e.g. `rust`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Step mode: which keys advance playback when air-typing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub air_keys: Option<String>,
+    /// Step mode: scripted actions played per air-typed key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actions_per_key: Option<usize>,
+    /// Editor profile: `plain` or `autoindent`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub editor: Option<String>,
+    /// Humanized typing speed in characters per second.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cps: Option<f64>,
+    /// Output backend: `os` or `humanize`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    /// Hash of the text the first track leaves behind, checked at load time
+    /// so an edit that changes the result does not go unnoticed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub final_hash: Option<String>,
+    /// Values for `{{name}}` placeholders, below `--set`.
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub vars: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TksMeta {
+    /// The playback settings as a configuration layer.
+    pub fn settings(&self) -> crate::config::AppConfig {
+        crate::config::AppConfig {
+            air_keys: self.air_keys.clone(),
+            actions_per_key: self.actions_per_key,
+            editor: self.editor.clone(),
+            backend: self.backend.clone(),
+            ..Default::default()
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TksTrack {
+    pub id: String,
+    pub sequence: Vec<TksEvent>,
+}
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", deny_unknown_fields)]
+pub enum TksEvent {
     #[serde(rename = "Insert")]
-    Insert { text: String, #[allow(dead_code)] t: Option<u64> },
+    Insert { text: String, t: Option<u64> },
 
     #[serde(rename = "Delete")]
-    Delete { #[serde(default = "one")] n: u32, #[allow(dead_code)] t: Option<u64> },
+    Delete { #[serde(default = "one")] n: u32, t: Option<u64> },
 
+    /// Starts a section running up to the next marker; an `optional` one can
+    /// be skipped live.
     #[serde(rename = "Marker")]
-    Marker { name: String, #[allow(dead_code)] t: Option<u64> },
+    Marker {
+        name: String,
+        note: Option<String>,
+        #[serde(default)]
+        optional: bool,
+        t: Option<u64>,
+    },
+
+    #[serde(rename = "Note")]
+    Note { text: String, t: Option<u64> },
+
+    /// Another script's track, or a raw text file, resolved at load time
+    /// relative to this script. Included markers are named `as/name`.
+    #[serde(rename = "Include")]
+    Include {
+        file: String,
+        track: Option<String>,
+        #[serde(rename = "as")]
+        ns: Option<String>,
+        t: Option<u64>,
+    },
+
+    /// Sends the actions that follow to an output target defined in the
+    /// config (`targets.<name>`); `""` is the default output.
+    #[serde(rename = "Target")]
+    Target { name: String, t: Option<u64> },
+
+    /// Alternative continuations chosen live when the playhead gets here;
+    /// the first path is the default.
+    #[serde(rename = "Branch")]
+    Branch { name: String, paths: Vec<TksPath>, t: Option<u64> },
+}
+
+/// One named alternative of a `Branch`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TksPath {
+    pub name: String,
+    pub sequence: Vec<TksEvent>,
+}
+
+impl TksEvent {
+    pub fn t(&self) -> Option<u64> {
+        match self {
+            Self::Insert { t, .. }
+            | Self::Delete { t, .. }
+            | Self::Marker { t, .. }
+            | Self::Note { t, .. }
+            | Self::Include { t, .. }
+            | Self::Target { t, .. }
+            | Self::Branch { t, .. } => *t,
+        }
+    }
+
+    pub fn with_t(mut self, time: Option<u64>) -> Self {
+        match &mut self {
+            Self::Insert { t, .. }
+            | Self::Delete { t, .. }
+            | Self::Marker { t, .. }
+            | Self::Note { t, .. }
+            | Self::Include { t, .. }
+            | Self::Target { t, .. }
+            | Self::Branch { t, .. } => *t = time,
+        }
+        self
+    }
+}
+
+/// Keys in a fixed order (`t`, `type`, then the payload) with defaults
+/// omitted, so writing a parsed file reproduces it.
+impl Serialize for TksEvent {
+    fn serialize<S: Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut m = s.serialize_map(None)?;
+        if let Some(t) = self.t() {
+            m.serialize_entry("t", &t)?;
+        }
+        match self {
+            Self::Insert { text, .. } => {
+                m.serialize_entry("type", "Insert")?;
+                m.serialize_entry("text", text)?;
+            }
+            Self::Delete { n, .. } => {
+                m.serialize_entry("type", "Delete")?;
+                if *n != 1 {
+                    m.serialize_entry("n", n)?;
+                }
+            }
+            Self::Marker { name, note, optional, .. } => {
+                m.serialize_entry("type", "Marker")?;
+                m.serialize_entry("name", name)?;
+                if let Some(note) = note {
+                    m.serialize_entry("note", note)?;
+                }
+                if *optional {
+                    m.serialize_entry("optional", optional)?;
+                }
+            }
+            Self::Note { text, .. } => {
+                m.serialize_entry("type", "Note")?;
+                m.serialize_entry("text", text)?;
+            }
+            Self::Include { file, track, ns, .. } => {
+                m.serialize_entry("type", "Include")?;
+                m.serialize_entry("file", file)?;
+                if let Some(track) = track {
+                    m.serialize_entry("track", track)?;
+                }
+                if let Some(ns) = ns {
+                    m.serialize_entry("as", ns)?;
+                }
+            }
+            Self::Target { name, .. } => {
+                m.serialize_entry("type", "Target")?;
+                m.serialize_entry("name", name)?;
+            }
+            Self::Branch { name, paths, .. } => {
+                m.serialize_entry("type", "Branch")?;
+                m.serialize_entry("name", name)?;
+                m.serialize_entry("paths", paths)?;
+            }
+        }
+        m.end()
+    }
+}
+
+impl TksFile {
+    /// Validates (upgrading older versions) and reports the first problem
+    /// as `name:line:column`.
+    /// `Include` events are kept as written.
+    pub fn parse(name: &str, bytes: &[u8]) -> Result<Self> {
+        validate::validate(bytes).map_err(|diags| first_error(name, diags))
+    }
+
+    /// Compact layout with one event per line, like `demo.tks.json`.
+    pub fn to_pretty(&self) -> String {
+        let mut out = String::from("{\n");
+        out += &format!("  \"version\": {},\n", json(&self.version));
+        if let Some(meta) = &self.meta {
+            out += &format!("  \"meta\": {},\n", json(meta));
+        }
+        out += "  \"tracks\": [";
+        for (i, track) in self.tracks.iter().enumerate() {
+            out += if i == 0 { "\n" } else { ",\n" };
+            out += "    {\n";
+            out += &format!("      \"id\": {},\n", json(&track.id));
+            out += "      \"sequence\": [";
+            for (j, ev) in track.sequence.iter().enumerate() {
+                out += if j == 0 { "\n" } else { ",\n" };
+                out += &format!("        {}", json(ev));
+            }
+            out += if track.sequence.is_empty() { "]\n" } else { "\n      ]\n" };
+            out += "    }";
+        }
+        out += if self.tracks.is_empty() { "]\n" } else { "\n  ]\n" };
+        out += "}\n";
+        out
+    }
 }
+
+fn first_error(name: &str, diags: Vec<validate::Diagnostic>) -> anyhow::Error {
+    let n = diags.len();
+    let first = diags.into_iter().next().expect("at least one diagnostic");
+    if n > 1 {
+        debug!("{} more problems in {}; run `monkey-typer check`", n - 1, name);
+    }
+    first.into_error(name).into()
+}
+
 fn one() -> u32 { 1 }
 
-#[derive(Debug, Clone, Copy)]
+fn json<T: Serialize + ?Sized>(v: &T) -> String {
+    serde_json::to_string(v).expect("strings and events always serialize")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Action {
     Char(char),
     Backspace,
 }
 
-#[derive(Debug, Clone)]
+/// A `Branch` laid out in the action stream: its paths follow each other
+/// from `at`, and playback continues at [`Branch::end`] after the chosen one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Branch {
+    pub name: String,
+    pub at: usize,
+    pub paths: Vec<(String, Range<usize>)>,
+}
+
+impl Branch {
+    pub fn end(&self) -> usize { self.paths.last().map_or(self.at, |(_, r)| r.end) }
+
+    /// Actions of the paths other than the default one.
+    pub fn others(&self) -> Range<usize> {
+        self.paths.first().map_or(self.at, |(_, r)| r.end)..self.end()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Sequence {
-    pub actions: Vec<Action>,
+    pub meta: TksMeta,
+    pub actions: Actions,
     pub markers: Vec<(usize, String)>,
+    /// Speaker notes, shown once the playhead reaches their position.
+    pub notes: Vec<(usize, String)>,
+    /// Markers whose section may be skipped.
+    pub optional: Vec<String>,
+    /// Ordered by `at`, outer branches before the ones nested in them.
+    pub branches: Vec<Branch>,
+    /// Output target switches: actions from the position on go to the named
+    /// target.
+    pub targets: Vec<(usize, String)>,
 }
 
 impl Sequence {
+    #[cfg(test)]
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        let f: TksFile = serde_json::from_slice(bytes)?;
-        let mut actions: Vec<Action> = Vec::new();
-        let mut markers: Vec<(usize, String)> = Vec::new();
+        Self::parse(std::path::Path::new("<input>"), bytes, &Vars::default())
+    }
+
+    /// Validates and flattens the script at `path`, resolving includes
+    /// relative to it and filling in placeholders.
+    pub fn parse(path: &std::path::Path, bytes: &[u8], vars: &Vars) -> Result<Self> {
+        let name = path.display().to_string();
+        let file = validate::validate_at(bytes, path, vars).map_err(|diags| first_error(&name, diags))?;
+        Ok(Self::flatten(&file))
+    }
 
-        if let Some(track) = f.tracks.get(0) {
+    /// Plays the first track into a flat action stream. Includes must have
+    /// been resolved already.
+    pub fn flatten(f: &TksFile) -> Self {
+        let mut seq = Self { meta: f.meta.clone().unwrap_or_default(), ..Self::default() };
+        if let Some(track) = f.tracks.first() {
             for ev in &track.sequence {
-                match ev {
-                    TksEvent::Insert { text, .. } => {
-                        for ch in text.chars() {
-                            actions.push(Action::Char(ch));
-                        }
+                seq.push_event(ev);
+            }
+        }
+        seq
+    }
+
+    /// Appends one event of the played track.
+    pub fn push_event(&mut self, ev: &TksEvent) {
+        let pos = self.actions.len();
+        match ev {
+            TksEvent::Insert { text, .. } => self.actions.push_text(text),
+            TksEvent::Delete { n, .. } => self.actions.push_delete(*n as usize),
+            TksEvent::Marker { name, note, optional, .. } => {
+                self.markers.push((pos, name.clone()));
+                if *optional {
+                    self.optional.push(name.clone());
+                }
+                if let Some(note) = note {
+                    self.notes.push((pos, note.clone()));
+                }
+            }
+            TksEvent::Note { text, .. } => self.notes.push((pos, text.clone())),
+            TksEvent::Include { file, .. } => debug!("unresolved include of {} skipped", file),
+            TksEvent::Target { name, .. } => self.targets.push((pos, name.clone())),
+            TksEvent::Branch { name, paths, .. } => {
+                let i = self.branches.len();
+                self.branches.push(Branch { name: name.clone(), at: pos, paths: Vec::new() });
+                let target = self.target_at(pos).to_string();
+                for path in paths {
+                    let start = self.actions.len();
+                    // every path starts on the target in use at the branch
+                    if self.target_at(start) != target {
+                        self.targets.push((start, target.clone()));
                     }
-                    TksEvent::Delete { n, .. } => {
-                        for _ in 0..*n {
-                            actions.push(Action::Backspace);
-                        }
+                    for ev in &path.sequence {
+                        self.push_event(ev);
                     }
-                    TksEvent::Marker { name, .. } => {
-                        markers.push((actions.len(), name.clone()));
+                    self.branches[i].paths.push((path.name.clone(), start..self.actions.len()));
+                }
+            }
+        }
+    }
+
+    /// Output target of the action at `pos`.
+    pub fn target_at(&self, pos: usize) -> &str {
+        self.targets.iter().rev().find(|(p, _)| *p <= pos).map_or("", |(_, name)| name.as_str())
+    }
+
+    /// Whether `pos` is only reached by taking a path other than the default.
+    pub fn off_default(&self, pos: usize) -> bool {
+        self.branches.iter().any(|b| b.others().contains(&pos))
+    }
+
+    /// The text left behind in the default output target once every action
+    /// has been played, taking the default path of each branch.
+    pub fn final_text(&self) -> String {
+        let mut shadow = crate::engine::shadow::ShadowBuffer::new();
+        for (pos, act) in self.actions.iter().enumerate() {
+            if !self.off_default(pos) && self.target_at(pos).is_empty() {
+                shadow.apply(act);
+            }
+        }
+        shadow.text().to_string()
+    }
+
+    /// Inverse of [`Sequence::flatten`]: runs of characters become `Insert`,
+    /// runs of backspaces `Delete { n }`, and markers, notes and target
+    /// switches go back to their positions (a note sharing a marker's position
+    /// is attached to it). Runs never span an anchor. Times are not
+    /// recovered, and a sequence with branches is refused: its paths are laid
+    /// out one after another and would come back as a single line of events.
+    pub fn unflatten(&self) -> Result<Vec<TksEvent>> {
+        if let Some(branch) = self.branches.first() {
+            anyhow::bail!("cannot unflatten branch {:?}: branches are not recovered", branch.name);
+        }
+        let mut markers = self.markers.iter().peekable();
+        let mut notes = self.notes.iter().peekable();
+        let mut targets = self.targets.iter().peekable();
+        let mut actions = self.actions.iter().peekable();
+        let mut events = Vec::new();
+        let mut i = 0;
+        loop {
+            while let Some((_, name)) = markers.next_if(|(p, _)| *p <= i) {
+                let note = notes.next_if(|(p, _)| *p <= i).map(|(_, n)| n.clone());
+                let optional = self.optional.contains(name);
+                events.push(TksEvent::Marker { name: name.clone(), note, optional, t: None });
+            }
+            while let Some((_, text)) = notes.next_if(|(p, _)| *p <= i) {
+                events.push(TksEvent::Note { text: text.clone(), t: None });
+            }
+            while let Some((_, name)) = targets.next_if(|(p, _)| *p <= i) {
+                events.push(TksEvent::Target { name: name.clone(), t: None });
+            }
+            let Some(first) = actions.next() else { break };
+            i += 1;
+
+            let next_anchor = [markers.peek(), notes.peek(), targets.peek()]
+                .into_iter()
+                .flatten()
+                .map(|(p, _)| *p)
+                .min()
+                .unwrap_or(usize::MAX);
+            match first {
+                Action::Char(ch) => {
+                    let mut text = String::from(ch);
+                    while i < next_anchor {
+                        let Some(Action::Char(ch)) = actions.next_if(|a| matches!(a, Action::Char(_))) else { break };
+                        text.push(ch);
+                        i += 1;
+                    }
+                    events.push(TksEvent::Insert { text, t: None });
+                }
+                Action::Backspace => {
+                    let mut n = 1;
+                    while i < next_anchor && actions.next_if(|a| *a == Action::Backspace).is_some() {
+                        n += 1;
+                        i += 1;
                     }
+                    events.push(TksEvent::Delete { n, t: None });
                 }
             }
         }
-        Ok(Self { actions, markers })
+        Ok(events)
     }
 }
 
@@ -84,10 +456,139 @@ mod tests {
         }"#;
         let seq = Sequence::from_bytes(json).unwrap();
         assert_eq!(seq.actions.len(), 4);
-        matches!(seq.actions[0], Action::Char('a'));
-        matches!(seq.actions[1], Action::Char('b'));
-        matches!(seq.actions[2], Action::Backspace);
-        matches!(seq.actions[3], Action::Char('c'));
+        assert_eq!(seq.actions.get(0), Some(Action::Char('a')));
+        assert_eq!(seq.actions.get(1), Some(Action::Char('b')));
+        assert_eq!(seq.actions.get(2), Some(Action::Backspace));
+        assert_eq!(seq.actions.get(3), Some(Action::Char('c')));
         assert_eq!(seq.markers[0].0, 4);
     }
+
+    #[test]
+    fn branches_lay_out_paths_one_after_another() {
+        let json = br#"{
+            "version":"2",
+            "tracks":[{"id":"main","sequence":[
+                {"type":"Insert","text":"a"},
+                {"type":"Branch","name":"depth","paths":[
+                    {"name":"quick","sequence":[{"type":"Insert","text":"q"}]},
+                    {"name":"deep","sequence":[
+                        {"type":"Insert","text":"dd"},
+                        {"type":"Branch","name":"inner","paths":[{"name":"x","sequence":[]},{"name":"y","sequence":[{"type":"Insert","text":"y"}]}]}
+                    ]}
+                ]},
+                {"type":"Insert","text":"!"}
+            ]}]
+        }"#;
+        let seq = Sequence::from_bytes(json).unwrap();
+        let text: String = seq.actions.iter().map(|a| if let Action::Char(c) = a { c } else { '?' }).collect();
+        assert_eq!(text, "aqddy!");
+        let outer = Branch { name: "depth".into(), at: 1, paths: vec![("quick".into(), 1..2), ("deep".into(), 2..5)] };
+        let inner = Branch { name: "inner".into(), at: 4, paths: vec![("x".into(), 4..4), ("y".into(), 4..5)] };
+        assert_eq!(seq.branches, vec![outer, inner]);
+        assert_eq!(seq.final_text(), "aq!");
+        assert!(seq.unflatten().unwrap_err().to_string().contains("\"depth\""));
+    }
+
+    #[test]
+    fn targets_switch_output_for_the_actions_after_them() {
+        let json = br#"{
+            "version":"2",
+            "tracks":[{"id":"main","sequence":[
+                {"type":"Insert","text":"fn"},
+                {"type":"Target","name":"shell"},
+                {"type":"Insert","text":"ls"},
+                {"type":"Target","name":""},
+                {"type":"Insert","text":"()"}
+            ]}]
+        }"#;
+        let seq = Sequence::from_bytes(json).unwrap();
+        assert_eq!(seq.targets, vec![(2, "shell".to_string()), (4, String::new())]);
+        assert_eq!((seq.target_at(1), seq.target_at(2), seq.target_at(4)), ("", "shell", ""));
+        assert_eq!(seq.final_text(), "fn()");
+        assert!(seq.unflatten().unwrap().contains(&TksEvent::Target { name: "shell".into(), t: None }));
+    }
+
+    #[test]
+    fn notes_from_markers_and_note_events() {
+        let json = br#"{
+            "version":"1",
+            "tracks":[{"id":"main","sequence":[
+                {"type":"Marker","name":"intro","note":"Say hello"},
+                {"type":"Insert","text":"ab"},
+                {"type":"Note","text":"Mention the borrow checker"},
+                {"type":"Marker","name":"end"}
+            ]}]
+        }"#;
+        let seq = Sequence::from_bytes(json).unwrap();
+        assert_eq!(seq.markers.len(), 2);
+        assert_eq!(
+            seq.notes,
+            vec![(0, "Say hello".to_string()), (2, "Mention the borrow checker".to_string())]
+        );
+    }
+
+    #[test]
+    fn writes_back_what_it_reads() {
+        let demo = include_str!("../../demo.tks.json");
+        assert_eq!(TksFile::parse("demo", demo.as_bytes()).unwrap().to_pretty(), demo);
+
+        let file = TksFile {
+            version: "2".into(),
+            meta: Some(TksMeta { title: Some("Demo".into()), cps: Some(9.5), ..Default::default() }),
+            tracks: vec![
+                TksTrack {
+                    id: "a".into(),
+                    sequence: vec![
+                        TksEvent::Insert { text: "x\"y\n".into(), t: None },
+                        TksEvent::Delete { n: 3, t: Some(4) },
+                        TksEvent::Marker { name: "m".into(), note: Some("say".into()), optional: true, t: None },
+                        TksEvent::Note { text: "later".into(), t: Some(9) },
+                    ],
+                },
+                TksTrack { id: "empty".into(), sequence: vec![] },
+            ],
+        };
+        let text = file.to_pretty();
+        assert!(text.contains("        {\"t\":4,\"type\":\"Delete\",\"n\":3},\n"));
+        assert!(text.contains("\"sequence\": []"));
+        assert!(text.contains("  \"meta\": {\"title\":\"Demo\",\"cps\":9.5},\n"));
+        assert_eq!(serde_json::from_str::<TksFile>(&text).unwrap(), file);
+    }
+
+    #[test]
+    fn unflatten_coalesces_runs_and_keeps_anchors() {
+        let demo = TksFile::parse("demo", include_bytes!("../../demo.tks.json")).unwrap();
+        let seq = Sequence::flatten(&demo);
+        let events = seq.unflatten().unwrap();
+        assert_eq!(
+            events,
+            vec![
+                TksEvent::Insert { text: "fn main() {\n    println!(\"hi\");".into(), t: None },
+                TksEvent::Delete { n: 5, t: None },
+                TksEvent::Insert { text: "hello\");\n}\n\n".into(), t: None },
+                TksEvent::Marker {
+                    name: "run".into(),
+                    note: Some("Switch to the terminal and cargo run".into()),
+                    optional: false,
+                    t: None,
+                },
+            ]
+        );
+
+        let round_trip = |seq: &Sequence| {
+            let track = TksTrack { id: "main".into(), sequence: seq.unflatten().unwrap() };
+            Sequence::flatten(&TksFile { version: "2".into(), meta: None, tracks: vec![track] })
+        };
+        assert_eq!(round_trip(&seq), seq);
+
+        let anchored = Sequence {
+            actions: vec![Action::Char('a'), Action::Char('b'), Action::Backspace, Action::Char('c')].into(),
+            markers: vec![(0, "start".into()), (1, "mid".into()), (4, "end".into())],
+            notes: vec![(1, "one".into()), (1, "two".into()), (3, "three".into())],
+            ..Default::default()
+        };
+        let events = anchored.unflatten().unwrap();
+        assert_eq!(events.len(), 9);
+        assert_eq!(round_trip(&anchored), anchored);
+    }
 }
@@ -0,0 +1,602 @@
+use super::include::Includes;
+use super::migrate;
+use super::template::{self, Vars};
+use super::tks_json::{Sequence, TksEvent, TksFile, TksMeta};
+use crate::errors::MonkeyTyperError;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// One problem in a script, located by 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn into_error(self, file: &str) -> MonkeyTyperError {
+        MonkeyTyperError::Invalid {
+            file: file.to_string(),
+            line: self.line,
+            column: self.column,
+            message: self.message,
+        }
+    }
+}
+
+/// Parses `bytes` strictly and runs the semantic checks; returns every
+/// problem found, in document order, or the parsed file if there are none.
+/// `Include` events are kept and not followed.
+pub fn validate(bytes: &[u8]) -> Result<TksFile, Vec<Diagnostic>> {
+    check(bytes, None)
+}
+
+/// Like [`validate`] for the script at `path`, with includes resolved and
+/// placeholders filled in: the returned tracks hold the included events,
+/// and problems inside them are reported at the `Include`.
+pub fn validate_at(bytes: &[u8], path: &std::path::Path, vars: &Vars) -> Result<TksFile, Vec<Diagnostic>> {
+    check(bytes, Some((path, vars)))
+}
+
+fn check(bytes: &[u8], resolve: Option<(&std::path::Path, &Vars)>) -> Result<TksFile, Vec<Diagnostic>> {
+    let located = |e: serde_json::Error| {
+        let message = e.to_string();
+        // serde_json appends " at line L column C"; we report it ourselves
+        let message = match message.rfind(" at line ") {
+            Some(i) => message[..i].to_string(),
+            None => message,
+        };
+        vec![Diagnostic { line: e.line().max(1), column: e.column().max(1), message }]
+    };
+    let mut doc: serde_json::Value = serde_json::from_slice(bytes).map_err(located)?;
+
+    let spans = Spans::scan(bytes);
+    let mut out = Vec::new();
+    let mut report = |ptr: &str, message: String| {
+        let (line, column) = spans.locate(bytes, ptr);
+        out.push(Diagnostic { line, column, message });
+    };
+
+    // older scripts are upgraded in memory; migrations keep event indices
+    // stable so locations below still point into the original text
+    let mut file: TksFile = match migrate::upgrade(&mut doc) {
+        Err(message) => {
+            report("/version", message);
+            return Err(out);
+        }
+        Ok(migrate::CURRENT) => serde_json::from_slice(bytes).map_err(located)?,
+        Ok(from) => match TksFile::deserialize(&doc) {
+            Ok(file) => file,
+            Err(e) => {
+                let (ptr, message) = failing_part(&doc).unwrap_or(("/version".to_string(), e.to_string()));
+                report(&ptr, format!("{message} (after upgrading from version {from})"));
+                return Err(out);
+            }
+        },
+    };
+    if file.tracks.is_empty() {
+        report("/tracks", "no tracks".to_string());
+    }
+
+    if let Some(meta) = &file.meta {
+        for (field, problem) in Checker::meta(meta) {
+            report(&format!("/meta{field}"), problem);
+        }
+    }
+
+    let meta = file.meta.clone().unwrap_or_default();
+    let mut resolve = resolve.map(|(path, vars)| (Includes::new(path), vars.with_meta(&meta)));
+    let mut checker = Checker::default();
+    for (i, track) in file.tracks.iter_mut().enumerate() {
+        checker.new_track();
+        if let Some(problem) = Checker::track_id(&track.id) {
+            report(&format!("/tracks/{i}/id"), problem);
+        }
+        let mut resolved = Vec::with_capacity(track.sequence.len());
+        for (j, ev) in track.sequence.iter().enumerate() {
+            let ptr = format!("/tracks/{i}/sequence/{j}");
+            // placeholders are filled in before includes are expanded, so
+            // raw included text is never rendered
+            let ev = match &resolve {
+                Some((_, vars)) => match vars.render_event(ev.clone()) {
+                    Ok(ev) => ev,
+                    Err(problem) => {
+                        match ev {
+                            TksEvent::Insert { .. } => report(&format!("{ptr}/text"), problem),
+                            _ => report(&ptr, problem),
+                        }
+                        continue;
+                    }
+                },
+                None => ev.clone(),
+            };
+            let events = match (&mut resolve, &ev) {
+                (Some((inc, vars)), TksEvent::Branch { .. }) => match inc.expand(&ev, vars) {
+                    Ok(events) => events.into_iter().map(|e| (e, None)).collect(),
+                    Err(problem) => {
+                        report(&ptr, problem);
+                        continue;
+                    }
+                },
+                (Some((inc, vars)), TksEvent::Include { file, .. }) => match inc.expand(&ev, vars) {
+                    Ok(events) => events,
+                    Err(problem) => {
+                        report(&format!("{ptr}/file"), problem);
+                        continue;
+                    }
+                }
+                .into_iter()
+                .map(|e| (e, Some(file.clone())))
+                .collect(),
+                _ => vec![(ev, None)],
+            };
+            for (ev, from) in events {
+                for (field, problem) in checker.event(&ev) {
+                    match &from {
+                        Some(file) => report(&ptr, format!("in {file}: {problem}")),
+                        None => report(&format!("{ptr}{field}"), problem),
+                    }
+                }
+                resolved.push(ev);
+            }
+        }
+        if resolve.is_some() {
+            track.sequence = resolved;
+        }
+    }
+
+    if let Some(problem) = file.meta.as_ref().and_then(|m| Checker::final_hash(m, &Sequence::flatten(&file))) {
+        report("/meta/final_hash", problem);
+    }
+
+    if out.is_empty() {
+        Ok(file)
+    } else {
+        Err(out)
+    }
+}
+
+/// Pointer to the first part of an upgraded document that does not
+/// deserialize, and why. Migrations keep indices stable, so the pointer
+/// also locates the problem in the original text.
+fn failing_part(doc: &serde_json::Value) -> Option<(String, String)> {
+    use serde_json::Value;
+
+    fn at(ptr: String, e: serde_json::Error) -> (String, String) {
+        let message = e.to_string();
+        let field = message.strip_prefix("unknown field `").and_then(|m| m.split('`').next());
+        match field {
+            Some(field) => (format!("{ptr}/{field}"), message),
+            None => (ptr, message),
+        }
+    }
+    fn events(list: &Value, ptr: &str) -> Option<(String, String)> {
+        for (j, ev) in list.as_array()?.iter().enumerate() {
+            let ptr = format!("{ptr}/{j}");
+            for (k, path) in ev.get("paths").and_then(Value::as_array).into_iter().flatten().enumerate() {
+                if let Some(found) = path.get("sequence").and_then(|s| events(s, &format!("{ptr}/paths/{k}/sequence"))) {
+                    return Some(found);
+                }
+            }
+            if let Err(e) = TksEvent::deserialize(ev) {
+                return Some(at(ptr, e));
+            }
+        }
+        None
+    }
+
+    if let Some(Err(e)) = doc.get("meta").map(TksMeta::deserialize) {
+        return Some(at("/meta".to_string(), e));
+    }
+    for (i, track) in doc.get("tracks")?.as_array()?.iter().enumerate() {
+        if let Some(found) = track.get("sequence").and_then(|s| events(s, &format!("/tracks/{i}/sequence"))) {
+            return Some(found);
+        }
+        if let Err(e) = super::tks_json::TksTrack::deserialize(track) {
+            return Some(at(format!("/tracks/{i}"), e));
+        }
+    }
+    None
+}
+
+/// Semantic checks fed one track and event at a time, shared by `validate`
+/// and the streaming loader.
+#[derive(Default)]
+pub struct Checker {
+    marker_names: HashSet<String>,
+    last_t: Option<u64>,
+    /// Characters typed so far into each output target.
+    typed: HashMap<String, usize>,
+    target: String,
+    /// An unresolved `Include` came before, so `typed` is a lower bound.
+    included: bool,
+}
+
+impl Checker {
+    /// Times, targets and typed text are per track; marker names are global.
+    pub fn new_track(&mut self) {
+        self.last_t = None;
+        self.typed.clear();
+        self.target.clear();
+        self.included = false;
+    }
+
+    fn typed(&mut self) -> &mut usize {
+        self.typed.entry(self.target.clone()).or_default()
+    }
+
+    pub fn track_id(id: &str) -> Option<String> {
+        id.trim().is_empty().then(|| "track id must not be empty".to_string())
+    }
+
+    /// Problems with the script metadata, each with the field it is about.
+    pub fn meta(meta: &TksMeta) -> Vec<(&'static str, String)> {
+        let mut out = Vec::new();
+        if let Some(cps) = meta.cps.filter(|c| !(c.is_finite() && *c > 0.0)) {
+            out.push(("/cps", format!("cps must be positive, not {cps}")));
+        }
+        if meta.actions_per_key == Some(0) {
+            out.push(("/actions_per_key", "actions_per_key must be at least 1".to_string()));
+        }
+        if let Some(b) = meta.backend.as_deref().filter(|b| !matches!(*b, "os" | "humanize")) {
+            out.push(("/backend", format!("unknown backend {b:?} (expected os or humanize)")));
+        }
+        if let Some(Err(e)) = meta.editor.as_deref().map(str::parse::<crate::output::editor::EditorProfile>) {
+            out.push(("/editor", e.to_string()));
+        }
+        for (name, value) in &meta.vars {
+            if !template::is_name(name) {
+                out.push(("/vars", format!("invalid variable name {name:?}")));
+            } else if value.is_object() || value.is_array() || value.is_null() {
+                out.push(("/vars", format!("variable {name:?} must be a string, number or boolean")));
+            }
+        }
+        out
+    }
+
+    /// Compares the played text against `meta.final_hash`, if given.
+    pub fn final_hash(meta: &TksMeta, seq: &Sequence) -> Option<String> {
+        let expected = meta.final_hash.as_deref()?;
+        let got = crate::engine::persist::script_hash(seq.final_text().as_bytes());
+        (!got.eq_ignore_ascii_case(expected)).then(|| format!("final text hash is {got}, not {expected}"))
+    }
+
+    /// Problems with the next event, each with the field it is about
+    /// (`"/t"`, `"/name"`, `"/paths/0/sequence/2/n"`, or `""` for the whole
+    /// event).
+    pub fn event(&mut self, ev: &TksEvent) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        if let Some(t) = ev.t() {
+            if let Some(prev) = self.last_t.filter(|prev| t < *prev) {
+                out.push(("/t".to_string(), format!("t goes backwards ({t} after {prev})")));
+            }
+            self.last_t = Some(t);
+        }
+        match ev {
+            TksEvent::Insert { text, .. } => *self.typed() += text.chars().count(),
+            TksEvent::Delete { n, .. } => {
+                let n = *n as usize;
+                let typed = *self.typed();
+                if n > typed && !self.included {
+                    out.push((String::new(), format!("Delete of {n} exceeds the {typed} characters typed so far")));
+                }
+                *self.typed() = typed.saturating_sub(n);
+            }
+            TksEvent::Marker { name, .. } => {
+                if !self.marker_names.insert(name.clone()) {
+                    out.push(("/name".to_string(), format!("duplicate marker name {name:?}")));
+                }
+            }
+            TksEvent::Note { .. } => {}
+            TksEvent::Include { file, .. } => {
+                if file.trim().is_empty() {
+                    out.push(("/file".to_string(), "Include needs a file".to_string()));
+                }
+                self.included = true;
+            }
+            TksEvent::Target { name, .. } => self.target = name.clone(),
+            TksEvent::Branch { paths, .. } => {
+                if paths.is_empty() {
+                    out.push(("/paths".to_string(), "Branch needs at least one path".to_string()));
+                }
+                // every path starts from here; afterwards only what all of
+                // them leave behind can be deleted
+                let (typed, target, last_t, included) = (self.typed.clone(), self.target.clone(), self.last_t, self.included);
+                let mut after: (Option<HashMap<String, usize>>, Option<String>, _, _) = (None, None, last_t, included);
+                let mut names = HashSet::new();
+                for (k, path) in paths.iter().enumerate() {
+                    if path.name.trim().is_empty() {
+                        out.push((format!("/paths/{k}/name"), "path name must not be empty".to_string()));
+                    } else if !names.insert(path.name.as_str()) {
+                        out.push((format!("/paths/{k}/name"), format!("duplicate path name {:?}", path.name)));
+                    }
+                    (self.typed, self.target, self.last_t, self.included) = (typed.clone(), target.clone(), last_t, included);
+                    for (m, inner) in path.sequence.iter().enumerate() {
+                        for (field, problem) in self.event(inner) {
+                            out.push((format!("/paths/{k}/sequence/{m}{field}"), problem));
+                        }
+                    }
+                    let mut left = std::mem::take(&mut self.typed);
+                    if let Some(prev) = after.0 {
+                        left.retain(|t, n| prev.get(t).map(|p| *n = (*n).min(*p)).is_some());
+                    }
+                    match &after.1 {
+                        Some(first) if *first != self.target => out.push((
+                            format!("/paths/{k}"),
+                            format!("path ends on target {:?}, not {first:?} like the first path", self.target),
+                        )),
+                        _ => {}
+                    }
+                    after = (Some(left), after.1.or(Some(self.target.clone())), after.2.max(self.last_t), after.3 || self.included);
+                }
+                self.typed = after.0.unwrap_or(typed);
+                self.target = after.1.unwrap_or(target);
+                (self.last_t, self.included) = (after.2, after.3);
+            }
+        }
+        out
+    }
+}
+
+/// Byte offset of every value in a (well-formed) JSON document, keyed by
+/// JSON pointer, so semantic errors can point back into the source.
+struct Spans(HashMap<String, usize>);
+
+impl Spans {
+    fn scan(bytes: &[u8]) -> Self {
+        let mut s = Scanner { b: bytes, i: 0, out: HashMap::new() };
+        s.value(String::new());
+        Self(s.out)
+    }
+
+    /// Line and column of `ptr`, falling back to its closest recorded parent.
+    fn locate(&self, bytes: &[u8], ptr: &str) -> (usize, usize) {
+        let mut ptr = ptr;
+        let offset = loop {
+            if let Some(off) = self.0.get(ptr) {
+                break *off;
+            }
+            match ptr.rfind('/') {
+                Some(i) => ptr = &ptr[..i],
+                None => break 0,
+            }
+        };
+        let before = &bytes[..offset.min(bytes.len())];
+        let line = before.iter().filter(|b| **b == b'\n').count() + 1;
+        let line_start = before.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        let column = String::from_utf8_lossy(&before[line_start..]).chars().count() + 1;
+        (line, column)
+    }
+}
+
+struct Scanner<'a> {
+    b: &'a [u8],
+    i: usize,
+    out: HashMap<String, usize>,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<u8> { self.b.get(self.i).copied() }
+
+    fn ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.i += 1;
+        }
+    }
+
+    fn value(&mut self, ptr: String) {
+        self.ws();
+        self.out.insert(ptr.clone(), self.i);
+        match self.peek() {
+            Some(b'{') => {
+                self.i += 1;
+                loop {
+                    self.ws();
+                    match self.peek() {
+                        Some(b',') => self.i += 1,
+                        Some(b'"') => {
+                            let key = self.string();
+                            self.ws();
+                            self.i += 1; // ':'
+                            self.value(format!("{ptr}/{key}"));
+                        }
+                        Some(_) => {
+                            self.i += 1; // '}'
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.i += 1;
+                let mut n = 0;
+                loop {
+                    self.ws();
+                    match self.peek() {
+                        Some(b',') => self.i += 1,
+                        Some(b']') => {
+                            self.i += 1;
+                            break;
+                        }
+                        Some(_) => {
+                            self.value(format!("{ptr}/{n}"));
+                            n += 1;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            _ => {
+                while !matches!(self.peek(), None | Some(b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r')) {
+                    self.i += 1;
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> String {
+        self.i += 1;
+        let start = self.i;
+        while let Some(c) = self.peek() {
+            match c {
+                b'"' => break,
+                b'\\' => self.i += 2,
+                _ => self.i += 1,
+            }
+        }
+        let s = String::from_utf8_lossy(&self.b[start..self.i.min(self.b.len())]).into_owned();
+        self.i += 1;
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diags(json: &str) -> Vec<(usize, usize, String)> {
+        validate(json.as_bytes())
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|d| (d.line, d.column, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn unknown_fields_and_types_are_located() {
+        let typo = "{\"version\":\"2\",\"tracks\":[{\"id\":\"main\",\"sequence\":[\n  {\"tyoe\":\"Insert\",\"text\":\"a\"}\n]}]}";
+        let d = diags(typo);
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].0, 2);
+        assert!(d[0].2.contains("type"), "{}", d[0].2);
+
+        let field = "{\"version\":\"2\",\"tracks\":[{\"id\":\"main\",\"sequence\":[\n  {\"type\":\"Insert\",\"txt\":\"a\"}\n]}]}";
+        assert!(diags(field)[0].2.contains("txt"));
+
+        let variant = "{\"version\":\"2\",\"tracks\":[{\"id\":\"main\",\"sequence\":[\n  {\"type\":\"Insrt\",\"text\":\"a\"}\n]}]}";
+        assert!(diags(variant)[0].2.contains("Insrt"));
+    }
+
+    #[test]
+    fn semantic_checks_report_every_problem() {
+        let json = r#"{
+  "version": "2",
+  "tracks": [
+    {
+      "id": " ",
+      "sequence": [
+        {"t":5,"type":"Insert","text":"ab"},
+        {"t":3,"type":"Delete","n":3},
+        {"type":"Marker","name":"m"},
+        {"type":"Marker","name":"m"}
+      ]
+    }
+  ]
+}"#;
+        assert_eq!(
+            diags(json),
+            vec![
+                (5, 13, "track id must not be empty".to_string()),
+                (8, 14, "t goes backwards (3 after 5)".to_string()),
+                (8, 9, "Delete of 3 exceeds the 2 characters typed so far".to_string()),
+                (10, 33, "duplicate marker name \"m\"".to_string()),
+            ]
+        );
+        assert!(validate(include_bytes!("../../demo.tks.json")).is_ok());
+    }
+
+    #[test]
+    fn branch_paths_are_checked_from_the_branch_point() {
+        let json = r#"{"version":"2","tracks":[{"id":"main","sequence":[
+{"type":"Insert","text":"ab"},
+{"type":"Branch","name":"b","paths":[
+  {"name":"quick","sequence":[{"type":"Delete","n":2}]},
+  {"name":"quick","sequence":[{"type":"Insert","text":"cd"},{"type":"Delete","n":3},{"type":"Marker","name":"m"}]}
+]},
+{"type":"Delete","n":1},
+{"type":"Marker","name":"m"},
+{"type":"Branch","name":"empty","paths":[]}
+]}]}"#;
+        let d = diags(json);
+        let messages: Vec<&str> = d.iter().map(|(_, _, m)| m.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "duplicate path name \"quick\"",
+                "Delete of 1 exceeds the 0 characters typed so far",
+                "duplicate marker name \"m\"",
+                "Branch needs at least one path",
+            ]
+        );
+        assert_eq!(d[0].0, 5);
+    }
+
+    #[test]
+    fn typed_text_is_counted_per_target() {
+        let json = r#"{"version":"2","tracks":[{"id":"main","sequence":[
+{"type":"Insert","text":"abc"},
+{"type":"Target","name":"shell"},
+{"type":"Insert","text":"x"},
+{"type":"Delete","n":2},
+{"type":"Branch","name":"b","paths":[
+  {"name":"stay","sequence":[]},
+  {"name":"leave","sequence":[{"type":"Target","name":""}]}
+]},
+{"type":"Target","name":""},
+{"type":"Delete","n":3}
+]}]}"#;
+        let d = diags(json);
+        let messages: Vec<&str> = d.iter().map(|(_, _, m)| m.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Delete of 2 exceeds the 1 characters typed so far",
+                "path ends on target \"\", not \"shell\" like the first path",
+            ]
+        );
+    }
+
+    #[test]
+    fn meta_settings_and_final_hash_are_checked() {
+        let script = |meta: &str| {
+            format!("{{\"version\":\"2\",\n\"meta\":{meta},\n\"tracks\":[{{\"id\":\"main\",\"sequence\":[{{\"type\":\"Insert\",\"text\":\"abc\"}},{{\"type\":\"Delete\"}}]}}]}}")
+        };
+        let hash = crate::engine::persist::script_hash(b"ab");
+        let ok = script(&format!(r#"{{"title":"Demo","editor":"autoindent","cps":20,"final_hash":"{hash}"}}"#));
+        assert_eq!(validate(ok.as_bytes()).unwrap().meta.unwrap().cps, Some(20.0));
+
+        let bad = script(r#"{"cps":0,"backend":"xdo","final_hash":"0000000000000000"}"#);
+        let d = diags(&bad);
+        assert_eq!(d.len(), 3, "{d:?}");
+        assert_eq!((d[0].0, d[0].1), (2, 15));
+        assert!(d[1].2.contains("unknown backend"));
+        assert_eq!(d[2].2, format!("final text hash is {hash}, not 0000000000000000"));
+        assert!(diags(&script(r#"{"speed":3}"#))[0].2.contains("unknown field `speed`"));
+    }
+
+    #[test]
+    fn older_versions_upgrade_and_newer_are_rejected() {
+        let v1 = r#"{"version":"1","tracks":[{"id":"main","sequence":[
+            {"type":"Insert","text":"{{not a placeholder}}"}
+        ]}]}"#;
+        let seq = Sequence::parse(std::path::Path::new("test"), v1.as_bytes(), &Vars::default()).unwrap();
+        assert_eq!(seq.final_text(), "{{not a placeholder}}");
+
+        // problems in an upgraded file are still reported where they are
+        let typo = "{\"version\":\"1\",\"tracks\":[{\"id\":\"main\",\"sequence\":[\n  {\"type\":\"Insert\",\"text\":\"a\"},\n  {\"type\":\"Insert\",\"txet\":\"b\"}\n]}]}";
+        let d = diags(typo);
+        assert_eq!((d[0].0, d[0].1), (3, 27), "{d:?}");
+        assert!(d[0].2.contains("unknown field `txet`") && d[0].2.contains("after upgrading from version 1"), "{}", d[0].2);
+
+        let v9 = "{\n  \"version\": \"9\",\n  \"tracks\": []\n}";
+        let d = diags(v9);
+        assert_eq!((d[0].0, d[0].1), (2, 14));
+        assert!(d[0].2.contains("newer than this build supports"), "{}", d[0].2);
+    }
+}
@@ -0,0 +1,177 @@
+use super::trigger::{Stamped, StampedRx, TriggerEvent, TriggerSource};
+use crate::engine::status::StatusHub;
+use crate::prelude::*;
+use crossbeam_channel::{unbounded, Sender};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Unix socket accepting one command per line:
+/// `start`, `step [N]`, `pause`, `resume`, `goto <marker>`, `choose <path>`,
+/// `skip` and `status`. `step N` is capped at the actions left.
+/// Every line gets a one-line reply: `ok`, `error: ...` or the status as JSON.
+pub struct ControlSocket {
+    rx: StampedRx,
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    /// Listens on `path`, replacing a stale socket left by a crashed
+    /// instance. Any other file there is left alone.
+    pub fn bind(path: &Path, hub: StatusHub) -> Result<Self> {
+        use std::os::unix::fs::FileTypeExt;
+        if let Ok(meta) = std::fs::symlink_metadata(path) {
+            if !meta.file_type().is_socket() {
+                anyhow::bail!("{} exists and is not a socket", path.display());
+            }
+            if UnixStream::connect(path).is_ok() {
+                anyhow::bail!("control socket {} is in use by another instance", path.display());
+            }
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        let (tx, rx) = unbounded::<Stamped>();
+
+        std::thread::spawn(move || {
+            for conn in listener.incoming().flatten() {
+                let tx = tx.clone();
+                let hub = hub.clone();
+                std::thread::spawn(move || serve(conn, &tx, &hub));
+            }
+        });
+
+        Ok(Self { rx: StampedRx::new(rx), path: path.to_path_buf() })
+    }
+
+    /// Binds [`default_socket_path`], or a per-process path next to it when
+    /// another instance already listens there.
+    pub fn bind_default(hub: StatusHub) -> Result<Self> {
+        let path = default_socket_path();
+        match Self::bind(&path, hub.clone()) {
+            Err(e) if UnixStream::connect(&path).is_ok() => {
+                let own = path.with_file_name(format!("monkey-typer-{}.sock", std::process::id()));
+                warn!("{:#}; using {}", e, own.display());
+                Self::bind(&own, hub)
+            }
+            bound => bound,
+        }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) { let _ = std::fs::remove_file(&self.path); }
+}
+
+impl TriggerSource for ControlSocket {
+    fn recv_stamped(&self, timeout: Option<Duration>) -> Option<Stamped> { self.rx.recv(timeout) }
+    fn is_closed(&self) -> bool { self.rx.is_closed() }
+}
+
+/// `$XDG_RUNTIME_DIR/monkey-typer.sock`, falling back to the temp dir.
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("monkey-typer.sock")
+}
+
+/// Sends a single command line and returns the reply (client side of `ctl`).
+pub fn send(path: &Path, line: &str) -> Result<String> {
+    let mut conn = UnixStream::connect(path)
+        .map_err(|e| anyhow::anyhow!("cannot connect to {}: {e}", path.display()))?;
+    writeln!(conn, "{}", line.trim())?;
+    let mut reply = String::new();
+    BufReader::new(conn).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+fn serve(conn: UnixStream, tx: &Sender<Stamped>, hub: &StatusHub) {
+    let Ok(mut out) = conn.try_clone() else { return };
+    for line in BufReader::new(conn).lines() {
+        let Ok(line) = line else { return };
+        if line.trim().is_empty() { continue; }
+        let reply = if line.trim() == "status" {
+            serde_json::to_string(&hub.latest()).unwrap_or_default()
+        } else {
+            let status = hub.latest();
+            match parse_line(&line, status.end.saturating_sub(status.pos)) {
+                Ok(events) => {
+                    debug!("control: {:?}", events);
+                    for ev in events { let _ = tx.send((ev, Instant::now())); }
+                    "ok".to_string()
+                }
+                Err(e) => format!("error: {e}"),
+            }
+        };
+        if writeln!(out, "{reply}").is_err() { return; }
+    }
+}
+
+/// The events for one command line; `left` is the number of actions not yet
+/// played.
+fn parse_line(line: &str, left: usize) -> std::result::Result<Vec<TriggerEvent>, String> {
+    let mut words = line.split_whitespace();
+    let cmd = words.next().unwrap_or_default();
+    let rest: Vec<&str> = words.collect();
+    let ev = match (cmd, rest.as_slice()) {
+        ("start", []) => TriggerEvent::Start,
+        ("step", []) => TriggerEvent::Step,
+        ("step", [n]) => {
+            let n: usize = n.parse().map_err(|_| format!("invalid step count {n:?}"))?;
+            return Ok(vec![TriggerEvent::Step; n.min(left)]);
+        }
+        ("pause", []) => TriggerEvent::Pause,
+        ("resume", []) => TriggerEvent::Resume,
+        ("goto", [_, ..]) => TriggerEvent::GotoMarker(rest.join(" ")),
+        ("choose", [_, ..]) => TriggerEvent::Choose(rest.join(" ")),
+        ("skip", []) => TriggerEvent::Skip,
+        _ => return Err(format!("unknown command {:?}", line.trim())),
+    };
+    Ok(vec![ev])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        let parse_line = |line| parse_line(line, 10);
+        let step = TriggerEvent::Step;
+        assert_eq!(parse_line("step"), Ok(vec![step.clone()]));
+        assert_eq!(parse_line("step 3"), Ok(vec![step.clone(), step.clone(), step]));
+        assert_eq!(parse_line("step 99999999999").unwrap().len(), 10);
+        assert_eq!(parse_line(" pause "), Ok(vec![TriggerEvent::Pause]));
+        assert_eq!(parse_line("goto deep dive"), Ok(vec![TriggerEvent::GotoMarker("deep dive".into())]));
+        assert!(parse_line("step x").is_err());
+        assert_eq!(parse_line("choose deep"), Ok(vec![TriggerEvent::Choose("deep".into())]));
+        assert_eq!(parse_line("skip"), Ok(vec![TriggerEvent::Skip]));
+        assert!(parse_line("goto").is_err());
+        assert!(parse_line("jump").is_err());
+    }
+
+    #[test]
+    fn ctl_client_round_trip() {
+        let path = std::env::temp_dir().join(format!("monkey-typer-test-{}.sock", std::process::id()));
+        let hub = StatusHub::new();
+        let sock = ControlSocket::bind(&path, hub).unwrap();
+
+        assert_eq!(send(sock.path(), "resume").unwrap(), "ok");
+        assert!(send(sock.path(), "status").unwrap().contains(r#""pos":0"#));
+        assert!(send(sock.path(), "bogus").unwrap().starts_with("error:"));
+        assert_eq!(sock.recv_timeout(Duration::from_secs(1)), Some(TriggerEvent::Resume));
+
+        drop(sock);
+        assert!(!path.exists());
+
+        // a mistyped --socket must not delete the file it names
+        std::fs::write(&path, "{}").unwrap();
+        let err = ControlSocket::bind(&path, StatusHub::new()).err().unwrap().to_string();
+        assert!(err.contains("is not a socket"), "{err}");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
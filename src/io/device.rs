@@ -0,0 +1,132 @@
+use super::keyboard::{DeviceBinding, DeviceKey, DeviceMatch};
+use super::trigger::{Stamped, StampedRx, TriggerSource};
+use crate::prelude::*;
+use crossbeam_channel::{unbounded, Sender};
+use evdev::{Device, InputEventKind, Key};
+use std::time::{Duration, Instant};
+
+/// Reads presenter clickers / foot pedals through evdev.
+///
+/// Bound devices are grabbed exclusively, so their keys only drive playback
+/// and never reach the focused window; the normal keyboard is left alone.
+pub struct DeviceTrigger {
+    rx: StampedRx,
+}
+
+impl DeviceTrigger {
+    pub fn open(bindings: &[DeviceBinding]) -> Result<Self> {
+        let (tx, rx) = unbounded::<Stamped>();
+        let mut devices: Vec<(std::path::PathBuf, Device)> = evdev::enumerate().collect();
+
+        for binding in bindings {
+            let idx = devices
+                .iter()
+                .position(|(_, dev)| matches(&binding.matcher, dev))
+                .ok_or_else(|| anyhow::anyhow!("no input device matches {:?}", binding.matcher))?;
+            let (path, mut dev) = devices.swap_remove(idx);
+            dev.grab()
+                .map_err(|e| anyhow::anyhow!("cannot grab {}: {e}", path.display()))?;
+            debug!("device {:?} bound at {}", dev.name(), path.display());
+
+            let binding = binding.clone();
+            let tx = tx.clone();
+            std::thread::spawn(move || pump(dev, &binding, &tx));
+        }
+
+        Ok(Self { rx: StampedRx::new(rx) })
+    }
+}
+
+impl TriggerSource for DeviceTrigger {
+    fn recv_stamped(&self, timeout: Option<Duration>) -> Option<Stamped> { self.rx.recv(timeout) }
+    fn is_closed(&self) -> bool { self.rx.is_closed() }
+}
+
+fn matches(m: &DeviceMatch, dev: &Device) -> bool {
+    match m {
+        DeviceMatch::Name(name) => dev
+            .name()
+            .is_some_and(|n| n.to_lowercase().contains(&name.to_lowercase())),
+        DeviceMatch::Id { vendor, product } => {
+            let id = dev.input_id();
+            id.vendor() == *vendor && id.product() == *product
+        }
+    }
+}
+
+fn pump(mut dev: Device, binding: &DeviceBinding, tx: &Sender<Stamped>) {
+    loop {
+        let Ok(events) = dev.fetch_events() else { return };
+        for ev in events {
+            // value 1 = press; 0 = release, 2 = autorepeat
+            let InputEventKind::Key(key) = ev.kind() else { continue };
+            if ev.value() != 1 { continue; }
+            let action = device_key(key).and_then(|k| binding.action_for(k));
+            if let Some(action) = action {
+                if tx.send((action.event(), Instant::now())).is_err() { return; }
+            }
+        }
+    }
+}
+
+fn device_key(key: Key) -> Option<DeviceKey> {
+    Some(match key {
+        Key::KEY_PAGEDOWN => DeviceKey::PageDown,
+        Key::KEY_PAGEUP   => DeviceKey::PageUp,
+        Key::KEY_LEFT     => DeviceKey::Left,
+        Key::KEY_RIGHT    => DeviceKey::Right,
+        Key::KEY_UP       => DeviceKey::Up,
+        Key::KEY_DOWN     => DeviceKey::Down,
+        Key::KEY_SPACE    => DeviceKey::Space,
+        Key::KEY_ENTER    => DeviceKey::Enter,
+        Key::KEY_B        => DeviceKey::B,
+        Key::KEY_DOT      => DeviceKey::Period,
+        Key::KEY_F5       => DeviceKey::F5,
+        Key::KEY_ESC      => DeviceKey::Esc,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::io::trigger::TriggerEvent;
+    use super::*;
+    use evdev::uinput::VirtualDeviceBuilder;
+    use evdev::{AttributeSet, BusType, EventType, InputEvent, InputId};
+
+    #[test]
+    #[ignore = "needs write access to /dev/uinput"]
+    fn virtual_clicker_drives_playback() {
+        let mut keys = AttributeSet::<Key>::new();
+        keys.insert(Key::KEY_PAGEDOWN);
+        keys.insert(Key::KEY_PAGEUP);
+        keys.insert(Key::KEY_B);
+        let mut clicker = VirtualDeviceBuilder::new()
+            .unwrap()
+            .name("monkey-typer test clicker")
+            .input_id(InputId::new(BusType::BUS_USB, 0x1d6b, 0x7e57, 1))
+            .with_keys(&keys)
+            .unwrap()
+            .build()
+            .unwrap();
+        // give udev a moment to create the event node
+        std::thread::sleep(Duration::from_millis(300));
+
+        let binding = DeviceBinding::parse("id=1d6b:7e57").unwrap();
+        let trig = DeviceTrigger::open(&[binding]).unwrap();
+
+        for key in [Key::KEY_PAGEDOWN, Key::KEY_PAGEUP, Key::KEY_B] {
+            clicker
+                .emit(&[
+                    InputEvent::new(EventType::KEY, key.code(), 1),
+                    InputEvent::new(EventType::KEY, key.code(), 0),
+                ])
+                .unwrap();
+        }
+
+        let t = Duration::from_secs(1);
+        assert_eq!(trig.recv_timeout(t), Some(TriggerEvent::Step));
+        assert_eq!(trig.recv_timeout(t), Some(TriggerEvent::Back));
+        assert_eq!(trig.recv_timeout(t), Some(TriggerEvent::PauseToggle));
+    }
+}
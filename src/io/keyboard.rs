@@ -1,14 +1,8 @@
+use super::trigger::{Stamped, StampedRx, TriggerEvent, TriggerSource};
+use crate::prelude::*;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use crossbeam_channel::Receiver;
-use std::time::Duration;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum TriggerEvent {
-    Start,
-    Step { from_alpha: bool },
-    PauseToggle,
-    Exit,
-}
+use crossterm::terminal;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TriggerKey { Space, Right, F11, F12, CtrlP, CtrlQ, CtrlS }
@@ -28,12 +22,14 @@ impl TriggerKey {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Hotkeys {
     pub start: TriggerKey,
     pub step:  TriggerKey,
     pub pause: TriggerKey,
     pub exit:  TriggerKey,
+    pub air:   AirKeys,
+    pub devices: Vec<DeviceBinding>,
 }
 impl Hotkeys {
     pub fn from_strings(step: &str, pause: &str, start: &str, exit: &str) -> Self {
@@ -42,61 +38,272 @@ impl Hotkeys {
             pause: TriggerKey::parse(pause),
             start: TriggerKey::parse(start),
             exit:  TriggerKey::parse(exit),
+            air:   AirKeys::LETTERS,
+            devices: Vec::new(),
+        }
+    }
+
+    pub fn with_air_keys(mut self, spec: &str) -> Result<Self> {
+        self.air = AirKeys::parse(spec)?;
+        Ok(self)
+    }
+
+    pub fn with_devices(mut self, specs: &[String]) -> Result<Self> {
+        for spec in specs {
+            self.devices.push(DeviceBinding::parse(spec)?);
         }
+        Ok(self)
     }
 }
 
-pub fn poll_trigger_local(timeout_ms: u64, keys: &Hotkeys) -> Option<TriggerEvent> {
-    if event::poll(Duration::from_millis(timeout_ms)).ok()? {
-        if let Ok(Event::Key(KeyEvent{code, modifiers, kind, ..})) = event::read() {
-            if !matches!(kind, KeyEventKind::Press) { return None; }
+/// Classes of printable keys that advance playback when air-typing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AirKeys {
+    pub letters: bool,
+    pub digits:  bool,
+    pub punct:   bool,
+    pub space:   bool,
+}
 
-            let is_start = match (keys.start, code, modifiers.contains(KeyModifiers::CONTROL)) {
-                (TriggerKey::CtrlS, KeyCode::Char('s' | 'S'), true) => true,
-                (TriggerKey::F11,   KeyCode::F(11),           _   ) => true,
-                _ => false,
-            };
-            if is_start { return Some(TriggerEvent::Start); }
+impl AirKeys {
+    pub const LETTERS: Self = Self { letters: true, digits: false, punct: false, space: false };
+    pub const PRINTABLE: Self = Self { letters: true, digits: true, punct: true, space: true };
+    pub const NONE: Self = Self { letters: false, digits: false, punct: false, space: false };
 
-            let is_exit = match (keys.exit, code, modifiers.contains(KeyModifiers::CONTROL)) {
-                (TriggerKey::CtrlQ, KeyCode::Char('q' | 'Q'), true) => true,
-                _ => false,
-            };
-            if is_exit { return Some(TriggerEvent::Exit); }
+    /// Parses `printable`, `none` or a comma-separated subset of
+    /// `letters,digits,punct,space`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        match spec.trim().to_ascii_lowercase().as_str() {
+            "printable" | "all" => return Ok(Self::PRINTABLE),
+            "none" | "" => return Ok(Self::NONE),
+            _ => {}
+        }
+        let mut keys = Self::NONE;
+        for class in spec.split(',').map(|c| c.trim().to_ascii_lowercase()) {
+            match class.as_str() {
+                "letters" => keys.letters = true,
+                "digits"  => keys.digits = true,
+                "punct"   => keys.punct = true,
+                "space"   => keys.space = true,
+                _ => anyhow::bail!("unknown air key class {class:?} (letters, digits, punct, space)"),
+            }
+        }
+        Ok(keys)
+    }
 
-            let is_pause = match (keys.pause, code, modifiers.contains(KeyModifiers::CONTROL)) {
-                (TriggerKey::CtrlP, KeyCode::Char('p' | 'P'), true) => true,
-                _ => false,
-            };
-            if is_pause { return Some(TriggerEvent::PauseToggle); }
+    pub fn accepts(&self, c: char) -> bool {
+        if c.is_alphabetic() {
+            self.letters
+        } else if c.is_numeric() {
+            self.digits
+        } else if c == ' ' {
+            self.space
+        } else {
+            self.punct && !c.is_control() && !c.is_whitespace()
+        }
+    }
+}
 
-            let is_step_single = match (keys.step, code) {
-                (TriggerKey::Right, KeyCode::Right)    => true,
-                (TriggerKey::Space, KeyCode::Char(' '))=> true,
-                (TriggerKey::F12,   KeyCode::F(12))    => true,
-                _ => false,
-            };
-            if is_step_single { return Some(TriggerEvent::Step { from_alpha: false }); }
+/// Keys sent by presenter clickers and foot pedals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKey { PageDown, PageUp, Left, Right, Up, Down, Space, Enter, B, Period, F5, Esc }
 
-            if let KeyCode::Char(c) = code {
-                if c.is_ascii_alphabetic() && !modifiers.contains(KeyModifiers::CONTROL) {
-                    return Some(TriggerEvent::Step { from_alpha: false });
+impl DeviceKey {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s.to_ascii_lowercase().as_str() {
+            "pagedown" => Self::PageDown,
+            "pageup"   => Self::PageUp,
+            "left"     => Self::Left,
+            "right"    => Self::Right,
+            "up"       => Self::Up,
+            "down"     => Self::Down,
+            "space"    => Self::Space,
+            "enter"    => Self::Enter,
+            "b"        => Self::B,
+            "period"   => Self::Period,
+            "f5"       => Self::F5,
+            "esc"      => Self::Esc,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceAction { Step, Back, Pause, Skip }
+
+impl DeviceAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        Some(match s.to_ascii_lowercase().as_str() {
+            "step"  => Self::Step,
+            "back"  => Self::Back,
+            "pause" => Self::Pause,
+            "skip"  => Self::Skip,
+            _ => return None,
+        })
+    }
+
+    pub fn event(self) -> TriggerEvent {
+        match self {
+            Self::Step  => TriggerEvent::Step,
+            Self::Back  => TriggerEvent::Back,
+            Self::Pause => TriggerEvent::PauseToggle,
+            Self::Skip  => TriggerEvent::Skip,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceMatch {
+    /// Case-insensitive substring of the device name.
+    Name(String),
+    Id { vendor: u16, product: u16 },
+}
+
+/// A dedicated input device whose keys drive playback instead of typing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceBinding {
+    pub matcher: DeviceMatch,
+    pub keys: Vec<(DeviceKey, DeviceAction)>,
+}
+
+impl DeviceBinding {
+    /// Parses `name=<substring>` or `id=<vendor>:<product>` (hex), optionally
+    /// followed by `,<key>=<step|back|pause|skip>` pairs. Without pairs the usual
+    /// clicker layout applies: PageDown steps, PageUp goes back, B pauses.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut parts = spec.split(',').map(str::trim);
+        let matcher = match parts.next().and_then(|p| p.split_once('=')) {
+            Some(("name", name)) if !name.is_empty() => DeviceMatch::Name(name.to_string()),
+            Some(("id", id)) => {
+                let (v, p) = id.split_once(':').unwrap_or((id, ""));
+                match (u16::from_str_radix(v, 16), u16::from_str_radix(p, 16)) {
+                    (Ok(vendor), Ok(product)) => DeviceMatch::Id { vendor, product },
+                    _ => anyhow::bail!("device {spec:?}: id must be <vendor>:<product> in hex"),
                 }
             }
+            _ => anyhow::bail!("device {spec:?}: expected name=<name> or id=<vendor>:<product>"),
+        };
+
+        let mut keys = Vec::new();
+        for pair in parts {
+            let (k, a) = pair.split_once('=').unwrap_or((pair, ""));
+            match (DeviceKey::parse(k), DeviceAction::parse(a)) {
+                (Some(k), Some(a)) => keys.push((k, a)),
+                _ => anyhow::bail!("device {spec:?}: invalid binding {pair:?}"),
+            }
+        }
+        if keys.is_empty() {
+            keys = vec![
+                (DeviceKey::PageDown, DeviceAction::Step),
+                (DeviceKey::PageUp, DeviceAction::Back),
+                (DeviceKey::B, DeviceAction::Pause),
+            ];
+        }
+        Ok(Self { matcher, keys })
+    }
+
+    pub fn action_for(&self, key: DeviceKey) -> Option<DeviceAction> {
+        self.keys.iter().find(|(k, _)| *k == key).map(|(_, a)| *a)
+    }
+}
+
+/// Crossterm poller reading keys from the controlling terminal (raw mode).
+pub struct LocalTrigger {
+    keys: Hotkeys,
+    closed: std::sync::atomic::AtomicBool,
+}
+
+impl LocalTrigger {
+    pub fn new(keys: Hotkeys) -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        Ok(Self { keys, closed: Default::default() })
+    }
+
+    /// A key within `timeout`; a terminal that cannot be read any more
+    /// closes the source.
+    fn read_key(&self, timeout: Duration) -> Option<TriggerEvent> {
+        let read = event::poll(timeout).and_then(|ready| ready.then(event::read).transpose());
+        match read {
+            Ok(Some(Event::Key(key))) => decode_local(key, &self.keys),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("cannot read the terminal: {}", e);
+                self.closed.store(true, std::sync::atomic::Ordering::Relaxed);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for LocalTrigger {
+    fn drop(&mut self) { let _ = terminal::disable_raw_mode(); }
+}
+
+/// Keys are read on the calling thread, so they are stamped when read.
+impl TriggerSource for LocalTrigger {
+    fn recv_stamped(&self, timeout: Option<Duration>) -> Option<Stamped> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if let Some(ev) = self.read_key(left.unwrap_or(Duration::from_secs(1))) { return Some((ev, Instant::now())); }
+            if self.is_closed() || left.is_some_and(|l| l.is_zero()) { return None; }
+        }
+    }
+
+    fn is_closed(&self) -> bool { self.closed.load(std::sync::atomic::Ordering::Relaxed) }
+}
+
+fn decode_local(key: KeyEvent, keys: &Hotkeys) -> Option<TriggerEvent> {
+    let KeyEvent { code, modifiers, kind, .. } = key;
+    if !matches!(kind, KeyEventKind::Press) { return None; }
+    let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+    let alt = modifiers.contains(KeyModifiers::ALT);
+
+    let is_start = matches!(
+        (keys.start, code, ctrl),
+        (TriggerKey::CtrlS, KeyCode::Char('s' | 'S'), true) | (TriggerKey::F11, KeyCode::F(11), _)
+    );
+    if is_start { return Some(TriggerEvent::Start); }
+
+    let is_exit = matches!((keys.exit, code, ctrl), (TriggerKey::CtrlQ, KeyCode::Char('q' | 'Q'), true));
+    if is_exit { return Some(TriggerEvent::Exit); }
+
+    let is_pause = matches!((keys.pause, code, ctrl), (TriggerKey::CtrlP, KeyCode::Char('p' | 'P'), true));
+    if is_pause { return Some(TriggerEvent::PauseToggle); }
+
+    let is_step_single = matches!(
+        (keys.step, code),
+        (TriggerKey::Right, KeyCode::Right)
+            | (TriggerKey::Space, KeyCode::Char(' '))
+            | (TriggerKey::F12, KeyCode::F(12))
+    );
+    if is_step_single { return Some(TriggerEvent::Step); }
+
+    // alt+1..9 picks a path at a branch, alt+n skips an optional section
+    match code {
+        KeyCode::Char(c @ '1'..='9') if alt && !ctrl => return Some(TriggerEvent::Choose(c.to_string())),
+        KeyCode::Char('n' | 'N') if alt && !ctrl => return Some(TriggerEvent::Skip),
+        _ => {}
+    }
+
+    // Raw mode consumes the key, so nothing leaks into the target.
+    if let KeyCode::Char(c) = code {
+        if !ctrl && !alt && keys.air.accepts(c) {
+            return Some(TriggerEvent::AirKey { erase: false });
         }
     }
     None
 }
 
 #[cfg(target_os = "macos")]
-pub struct GlobalTrigger { rx: Receiver<TriggerEvent> }
+pub struct GlobalTrigger { rx: StampedRx }
 
 #[cfg(target_os = "macos")]
 impl GlobalTrigger {
     pub fn new(keys: Hotkeys) -> Self {
         use core_foundation::runloop::CFRunLoop;
         use core_graphics::event::{
-            CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement,
+            CGEvent, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement,
             CGEventType, EventField, KeyCode, CGEventFlags, CallbackResult,
         };
         use crossbeam_channel::unbounded;
@@ -105,7 +312,7 @@ impl GlobalTrigger {
             Arc,
         };
 
-        let (tx, rx) = unbounded::<TriggerEvent>();
+        let (tx, rx) = unbounded::<Stamped>();
 
         let armed = Arc::new(AtomicBool::new(false));
         let armed_cb = armed.clone();
@@ -113,77 +320,134 @@ impl GlobalTrigger {
         let my_pid: u32 = std::process::id();
 
         std::thread::spawn(move || {
-            let events = vec![CGEventType::KeyDown];
-
-            let _tap = CGEventTap::with_enabled(
-                CGEventTapLocation::HID,
-                CGEventTapPlacement::HeadInsertEventTap,
-                CGEventTapOptions::Default,
-                events,
-                move |_proxy, etype, event| {
-                    if !matches!(etype, CGEventType::KeyDown) {
-                        return CallbackResult::Keep;
-                    }
+            // `swallow` is false for a listen-only tap: air-typed keys then reach
+            // the focused app and have to be erased before injecting.
+            let on_key = |etype: CGEventType, event: &CGEvent, swallow: bool| {
+                if !matches!(etype, CGEventType::KeyDown) {
+                    return CallbackResult::Keep;
+                }
 
-                    let autorep = event.get_integer_value_field(EventField::KEYBOARD_EVENT_AUTOREPEAT) != 0;
-                    if autorep {
-                        return CallbackResult::Drop;
-                    }
+                let autorep = event.get_integer_value_field(EventField::KEYBOARD_EVENT_AUTOREPEAT) != 0;
+                if autorep {
+                    return CallbackResult::Drop;
+                }
 
-                    let src_pid = event.get_integer_value_field(EventField::EVENT_SOURCE_UNIX_PROCESS_ID) as u32;
-                    if src_pid == my_pid {
-                        return CallbackResult::Keep;
-                    }
+                let src_pid = event.get_integer_value_field(EventField::EVENT_SOURCE_UNIX_PROCESS_ID) as u32;
+                if src_pid == my_pid {
+                    return CallbackResult::Keep;
+                }
 
-                    let keycode = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE) as u16;
-                    let flags   = event.get_flags();
-                    let ctrl    = flags.contains(CGEventFlags::CGEventFlagControl);
+                let keycode = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE) as u16;
+                let flags   = event.get_flags();
+                let ctrl    = flags.contains(CGEventFlags::CGEventFlagControl);
+                let alt     = flags.contains(CGEventFlags::CGEventFlagAlternate);
+                let cmd     = flags.contains(CGEventFlags::CGEventFlagCommand);
 
-                    if ctrl && keycode == KeyCode::ANSI_S && matches!(keys.start, TriggerKey::CtrlS) {
-                        let _ = tx.send(TriggerEvent::Start);
-                        armed_cb.store(true, Ordering::Relaxed);
-                        return CallbackResult::Drop;
-                    }
-                    if ctrl && keycode == KeyCode::ANSI_P && matches!(keys.pause, TriggerKey::CtrlP) {
-                        let _ = tx.send(TriggerEvent::PauseToggle);
-                        return CallbackResult::Drop;
-                    }
-                    if ctrl && keycode == KeyCode::ANSI_Q && matches!(keys.exit, TriggerKey::CtrlQ) {
-                        let _ = tx.send(TriggerEvent::Exit);
-                        return CallbackResult::Drop;
-                    }
+                if ctrl && keycode == KeyCode::ANSI_S && matches!(keys.start, TriggerKey::CtrlS) {
+                    let _ = tx.send((TriggerEvent::Start, Instant::now()));
+                    armed_cb.store(true, Ordering::Relaxed);
+                    return CallbackResult::Drop;
+                }
+                if ctrl && keycode == KeyCode::ANSI_P && matches!(keys.pause, TriggerKey::CtrlP) {
+                    let _ = tx.send((TriggerEvent::PauseToggle, Instant::now()));
+                    return CallbackResult::Drop;
+                }
+                if ctrl && keycode == KeyCode::ANSI_Q && matches!(keys.exit, TriggerKey::CtrlQ) {
+                    let _ = tx.send((TriggerEvent::Exit, Instant::now()));
+                    return CallbackResult::Drop;
+                }
 
-                    if !armed_cb.load(Ordering::Relaxed) {
-                        return CallbackResult::Keep;
-                    }
+                if !armed_cb.load(Ordering::Relaxed) {
+                    return CallbackResult::Keep;
+                }
 
-                    let step_single = match keys.step {
-                        TriggerKey::F12   => keycode == KeyCode::F12,
-                        TriggerKey::Space => keycode == KeyCode::SPACE,
-                        TriggerKey::Right => keycode == KeyCode::RIGHT_ARROW,
-                        _ => false,
-                    };
-                    if step_single {
-                        let _ = tx.send(TriggerEvent::Step { from_alpha: false });
+                let step_single = match keys.step {
+                    TriggerKey::F12   => keycode == KeyCode::F12,
+                    TriggerKey::Space => keycode == KeyCode::SPACE,
+                    TriggerKey::Right => keycode == KeyCode::RIGHT_ARROW,
+                    _ => false,
+                };
+                if step_single {
+                    let _ = tx.send((TriggerEvent::Step, Instant::now()));
+                    return CallbackResult::Drop;
+                }
+
+                if alt && !ctrl && !cmd {
+                    if let Some(n) = digit_keycode(keycode).filter(|n| *n > 0) {
+                        let _ = tx.send((TriggerEvent::Choose(n.to_string()), Instant::now()));
                         return CallbackResult::Drop;
                     }
-
-                    if !ctrl && is_alpha_keycode(keycode) {
-                        let _ = tx.send(TriggerEvent::Step { from_alpha: false });
+                    if keycode == KeyCode::ANSI_N {
+                        let _ = tx.send((TriggerEvent::Skip, Instant::now()));
                         return CallbackResult::Drop;
                     }
+                }
+
+                let air = air_keycode_class(keycode).is_some_and(|c| keys.air.accepts(c));
+                if !ctrl && !alt && !cmd && air {
+                    let _ = tx.send((TriggerEvent::AirKey { erase: !swallow }, Instant::now()));
+                    return CallbackResult::Drop;
+                }
+
+                CallbackResult::Keep
+            };
 
-                    CallbackResult::Keep
-                },
-                || CFRunLoop::run_current(),
-            ).expect("Failed to install CGEventTap (Accessibility / Input Monitoring を許可してください)");
+            let run_tap = |options: CGEventTapOptions, swallow: bool| {
+                CGEventTap::with_enabled(
+                    CGEventTapLocation::HID,
+                    CGEventTapPlacement::HeadInsertEventTap,
+                    options,
+                    vec![CGEventType::KeyDown],
+                    |_proxy, etype, event| on_key(etype, event, swallow),
+                    || CFRunLoop::run_current(),
+                )
+            };
 
+            if run_tap(CGEventTapOptions::Default, true).is_err() {
+                eprintln!("Filtering event tap unavailable; falling back to listen-only (air-typed keys will be erased).");
+                run_tap(CGEventTapOptions::ListenOnly, false)
+                    .expect("Failed to install CGEventTap (Accessibility / Input Monitoring を許可してください)");
+            }
         });
 
-        Self { rx }
+        Self { rx: StampedRx::new(rx) }
     }
+}
+
+impl TriggerSource for GlobalTrigger {
+    fn recv_stamped(&self, timeout: Option<Duration>) -> Option<Stamped> { self.rx.recv(timeout) }
+    fn is_closed(&self) -> bool { self.rx.is_closed() }
+}
+
+/// Representative character for an air-typing key class (`a`, `0`, `.` or space).
+#[cfg(target_os = "macos")]
+fn air_keycode_class(code: u16) -> Option<char> {
+    use core_graphics::event::KeyCode;
+    if is_alpha_keycode(code) { return Some('a'); }
+    match code {
+        KeyCode::ANSI_0 | KeyCode::ANSI_1 | KeyCode::ANSI_2 | KeyCode::ANSI_3 |
+        KeyCode::ANSI_4 | KeyCode::ANSI_5 | KeyCode::ANSI_6 | KeyCode::ANSI_7 |
+        KeyCode::ANSI_8 | KeyCode::ANSI_9 => Some('0'),
+        KeyCode::ANSI_MINUS | KeyCode::ANSI_EQUAL | KeyCode::ANSI_LEFT_BRACKET |
+        KeyCode::ANSI_RIGHT_BRACKET | KeyCode::ANSI_QUOTE | KeyCode::ANSI_SEMICOLON |
+        KeyCode::ANSI_BACKSLASH | KeyCode::ANSI_COMMA | KeyCode::ANSI_SLASH |
+        KeyCode::ANSI_PERIOD | KeyCode::ANSI_GRAVE => Some('.'),
+        KeyCode::SPACE => Some(' '),
+        _ => None,
+    }
+}
 
-    pub fn try_recv(&self) -> Option<TriggerEvent> { self.rx.try_recv().ok() }
+/// The digit on a top-row number key.
+#[cfg(target_os = "macos")]
+fn digit_keycode(code: u16) -> Option<u8> {
+    use core_graphics::event::KeyCode;
+    [
+        KeyCode::ANSI_0, KeyCode::ANSI_1, KeyCode::ANSI_2, KeyCode::ANSI_3, KeyCode::ANSI_4,
+        KeyCode::ANSI_5, KeyCode::ANSI_6, KeyCode::ANSI_7, KeyCode::ANSI_8, KeyCode::ANSI_9,
+    ]
+    .iter()
+    .position(|k| *k == code)
+    .map(|n| n as u8)
 }
 
 #[cfg(target_os = "macos")]
@@ -202,13 +466,66 @@ fn is_alpha_keycode(code: u16) -> bool {
 }
 
 #[cfg(not(target_os = "macos"))]
-pub struct GlobalTrigger { rx: Receiver<TriggerEvent> }
+pub struct GlobalTrigger { rx: StampedRx, _tx: crossbeam_channel::Sender<Stamped> }
 #[cfg(not(target_os = "macos"))]
 impl GlobalTrigger {
     pub fn new(_keys: Hotkeys) -> Self {
-        let (_tx, rx) = unbounded();
+        let (_tx, rx) = crossbeam_channel::unbounded();
         eprintln!("GlobalTrigger is only available on macOS.");
-        Self { rx }
+        Self { rx: StampedRx::new(rx), _tx }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn air_keys_classes() {
+        let keys = AirKeys::parse("letters, digits").unwrap();
+        assert!(keys.accepts('x') && keys.accepts('7'));
+        assert!(!keys.accepts(';') && !keys.accepts(' '));
+
+        let all = AirKeys::parse("printable").unwrap();
+        assert!(all.accepts('{') && all.accepts(' ') && all.accepts('é'));
+        assert!(!all.accepts('\n'));
+        assert!(AirKeys::parse("emoji").is_err());
+    }
+
+    #[test]
+    fn local_air_typing_respects_hotkeys_and_modifiers() {
+        let keys = Hotkeys::from_strings("right", "ctrl+p", "ctrl+s", "ctrl+q")
+            .with_air_keys("printable")
+            .unwrap();
+        let press = |code, mods| decode_local(KeyEvent::new(code, mods), &keys);
+
+        assert_eq!(press(KeyCode::Char('1'), KeyModifiers::NONE), Some(TriggerEvent::AirKey { erase: false }));
+        assert_eq!(press(KeyCode::Char('('), KeyModifiers::SHIFT), Some(TriggerEvent::AirKey { erase: false }));
+        assert_eq!(press(KeyCode::Char('p'), KeyModifiers::CONTROL), Some(TriggerEvent::PauseToggle));
+        assert_eq!(press(KeyCode::Char('x'), KeyModifiers::ALT), None);
+        assert_eq!(press(KeyCode::Char('2'), KeyModifiers::ALT), Some(TriggerEvent::Choose("2".into())));
+        assert_eq!(press(KeyCode::Char('n'), KeyModifiers::ALT), Some(TriggerEvent::Skip));
+        assert_eq!(press(KeyCode::Right, KeyModifiers::NONE), Some(TriggerEvent::Step));
+    }
+
+    #[test]
+    fn device_binding_defaults_to_clicker_layout() {
+        let b = DeviceBinding::parse("name=Logitech R400").unwrap();
+        assert_eq!(b.matcher, DeviceMatch::Name("Logitech R400".into()));
+        assert_eq!(b.action_for(DeviceKey::PageDown), Some(DeviceAction::Step));
+        assert_eq!(b.action_for(DeviceKey::PageUp), Some(DeviceAction::Back));
+        assert_eq!(b.action_for(DeviceKey::B), Some(DeviceAction::Pause));
+    }
+
+    #[test]
+    fn device_binding_with_id_and_custom_keys() {
+        let b = DeviceBinding::parse("id=046d:c538, right=step, left=back").unwrap();
+        assert_eq!(b.matcher, DeviceMatch::Id { vendor: 0x046d, product: 0xc538 });
+        assert_eq!(b.action_for(DeviceKey::Right), Some(DeviceAction::Step));
+        assert_eq!(b.action_for(DeviceKey::PageDown), None);
+
+        assert!(DeviceBinding::parse("id=xyz").is_err());
+        assert!(DeviceBinding::parse("serial=1").is_err());
+        assert!(DeviceBinding::parse("name=pedal,pagedown=jump").is_err());
     }
-    pub fn try_recv(&self) -> Option<TriggerEvent> { self.rx.try_recv().ok() }
 }
@@ -0,0 +1,227 @@
+use super::trigger::{Stamped, StampedRx, TriggerEvent, TriggerSource};
+use crate::engine::status::{Status, StatusHub};
+use crate::prelude::*;
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Small HTTP server turning remote commands into `TriggerEvent`s.
+///
+/// Commands are `POST`s: `/start`, `/step`, `/back`, `/pause`, `/resume`,
+/// `/toggle`, `/skip`, `/seek?pos=N`, `/marker?name=NAME` and
+/// `/choose?path=NAME`. All but `/step`, `/back` and `/toggle` are safe to
+/// retry. `GET /status` returns the current `Status` as JSON and
+/// `GET /events` streams it as server-sent events. When a token is set it must
+/// be passed as `Authorization: Bearer <token>` or a `token=` query parameter.
+pub struct RemoteTrigger {
+    rx: StampedRx,
+    addr: SocketAddr,
+}
+
+impl RemoteTrigger {
+    pub fn start(addr: &str, token: Option<String>, hub: StatusHub) -> Result<Self> {
+        let server = Server::http(addr).map_err(|e| anyhow::anyhow!("remote: bind {addr}: {e}"))?;
+        let addr = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| anyhow::anyhow!("remote: {addr} is not an IP address"))?;
+        let (tx, rx) = unbounded::<Stamped>();
+
+        std::thread::spawn(move || {
+            for req in server.incoming_requests() {
+                handle(req, token.as_deref(), &tx, &hub);
+            }
+        });
+
+        Ok(Self { rx: StampedRx::new(rx), addr })
+    }
+
+    pub fn addr(&self) -> SocketAddr { self.addr }
+}
+
+impl TriggerSource for RemoteTrigger {
+    fn recv_stamped(&self, timeout: Option<Duration>) -> Option<Stamped> { self.rx.recv(timeout) }
+    fn is_closed(&self) -> bool { self.rx.is_closed() }
+}
+
+fn handle(req: Request, token: Option<&str>, tx: &Sender<Stamped>, hub: &StatusHub) {
+    let (path, query) = split_url(req.url());
+    let path = path.to_string();
+    let query = query.to_string();
+
+    if let Some(token) = token {
+        let bearer = format!("Bearer {token}");
+        let by_header = req
+            .headers()
+            .iter()
+            .any(|h| h.field.equiv("Authorization") && same(h.value.as_str(), &bearer));
+        let by_query = query_param(&query, "token").is_some_and(|t| same(&percent_decode(t), token));
+        if !by_header && !by_query {
+            return reply(req, 401, r#"{"error":"unauthorized"}"#);
+        }
+    }
+
+    match (req.method(), path.as_str()) {
+        (Method::Get, "/status") => {
+            let body = serde_json::to_string(&hub.latest()).unwrap_or_default();
+            reply(req, 200, &body)
+        }
+        (Method::Get, "/events") => {
+            let updates = hub.subscribe();
+            std::thread::spawn(move || stream_events(req, updates));
+        }
+        (Method::Post, _) => match parse_command(&path, &query) {
+            Ok(ev) => {
+                debug!("remote: {:?}", ev);
+                let _ = tx.send((ev, Instant::now()));
+                reply(req, 200, r#"{"ok":true}"#)
+            }
+            Err(code) => reply(req, code, r#"{"error":"bad request"}"#),
+        },
+        _ => reply(req, 404, r#"{"error":"not found"}"#),
+    }
+}
+
+fn parse_command(path: &str, query: &str) -> std::result::Result<TriggerEvent, u16> {
+    match path {
+        "/start" => Ok(TriggerEvent::Start),
+        "/step" => Ok(TriggerEvent::Step),
+        "/back" => Ok(TriggerEvent::Back),
+        "/pause" => Ok(TriggerEvent::Pause),
+        "/resume" => Ok(TriggerEvent::Resume),
+        "/toggle" => Ok(TriggerEvent::PauseToggle),
+        "/skip" => Ok(TriggerEvent::Skip),
+        "/seek" => query_param(query, "pos")
+            .and_then(|p| p.parse().ok())
+            .map(TriggerEvent::Seek)
+            .ok_or(400),
+        "/marker" => query_param(query, "name")
+            .map(|n| TriggerEvent::GotoMarker(percent_decode(n)))
+            .ok_or(400),
+        "/choose" => query_param(query, "path")
+            .map(|p| TriggerEvent::Choose(percent_decode(p)))
+            .ok_or(400),
+        _ => Err(404),
+    }
+}
+
+fn stream_events(req: Request, updates: Receiver<Status>) {
+    let mut w = req.into_writer();
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n";
+    if w.write_all(head.as_bytes()).and_then(|_| w.flush()).is_err() { return; }
+    for status in updates {
+        let json = serde_json::to_string(&status).unwrap_or_default();
+        if write!(w, "data: {json}\n\n").and_then(|_| w.flush()).is_err() { return; }
+    }
+}
+
+fn reply(req: Request, code: u16, body: &str) {
+    let ct = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let _ = req.respond(Response::from_string(body).with_status_code(code).with_header(ct));
+}
+
+fn split_url(url: &str) -> (&str, &str) {
+    url.split_once('?').unwrap_or((url, ""))
+}
+
+fn query_param<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+/// Compares secrets in time independent of where they differ.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 2;
+            }
+            (b'+', _) => out.push(b' '),
+            (b, _) => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpStream;
+
+    fn request(addr: SocketAddr, method: &str, target: &str) -> String {
+        let mut s = TcpStream::connect(addr).unwrap();
+        write!(s, "{method} {target} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n\r\n").unwrap();
+        let mut out = String::new();
+        s.read_to_string(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn commands_become_trigger_events() {
+        let remote = RemoteTrigger::start("127.0.0.1:0", None, StatusHub::new()).unwrap();
+        let addr = remote.addr();
+
+        assert!(request(addr, "POST", "/step").starts_with("HTTP/1.1 200"));
+        assert!(request(addr, "POST", "/seek?pos=12").starts_with("HTTP/1.1 200"));
+        assert!(request(addr, "POST", "/marker?name=deep%20dive").starts_with("HTTP/1.1 200"));
+        assert!(request(addr, "POST", "/choose?path=2").starts_with("HTTP/1.1 200"));
+        assert!(request(addr, "POST", "/pause").starts_with("HTTP/1.1 200"));
+        assert!(request(addr, "POST", "/resume").starts_with("HTTP/1.1 200"));
+        assert!(request(addr, "POST", "/back").starts_with("HTTP/1.1 200"));
+        assert!(request(addr, "POST", "/seek").starts_with("HTTP/1.1 400"));
+        assert!(request(addr, "POST", "/nope").starts_with("HTTP/1.1 404"));
+
+        let t = Duration::from_secs(1);
+        assert_eq!(remote.recv_timeout(t), Some(TriggerEvent::Step));
+        assert_eq!(remote.recv_timeout(t), Some(TriggerEvent::Seek(12)));
+        assert_eq!(remote.recv_timeout(t), Some(TriggerEvent::GotoMarker("deep dive".into())));
+        assert_eq!(remote.recv_timeout(t), Some(TriggerEvent::Choose("2".into())));
+        assert_eq!(remote.recv_timeout(t), Some(TriggerEvent::Pause));
+        assert_eq!(remote.recv_timeout(t), Some(TriggerEvent::Resume));
+        assert_eq!(remote.recv_timeout(t), Some(TriggerEvent::Back));
+        assert_eq!(remote.recv_timeout(Duration::from_millis(50)), None);
+    }
+
+    #[test]
+    fn token_is_required_when_set() {
+        let remote = RemoteTrigger::start("127.0.0.1:0", Some("s3cret&=".into()), StatusHub::new()).unwrap();
+        let addr = remote.addr();
+
+        assert!(request(addr, "POST", "/toggle").starts_with("HTTP/1.1 401"));
+        assert!(request(addr, "POST", "/toggle?token=s3cret").starts_with("HTTP/1.1 401"));
+        assert!(request(addr, "POST", "/toggle?token=s3cret%26%3D").starts_with("HTTP/1.1 200"));
+        assert_eq!(remote.recv_timeout(Duration::from_secs(1)), Some(TriggerEvent::PauseToggle));
+    }
+
+    #[test]
+    fn events_stream_status_updates() {
+        let hub = StatusHub::new();
+        let remote = RemoteTrigger::start("127.0.0.1:0", None, hub.clone()).unwrap();
+
+        let mut s = TcpStream::connect(remote.addr()).unwrap();
+        write!(s, "GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut lines = BufReader::new(s).lines().map(|l| l.unwrap());
+        assert!(lines.next().unwrap().starts_with("HTTP/1.1 200"));
+
+        let mut data = lines.filter(|l| l.starts_with("data: "));
+        assert!(data.next().unwrap().contains(r#""pos":0"#));
+        hub.publish(Status { started: true, pos: 3, end: 9, ..Default::default() });
+        assert!(data.next().unwrap().contains(r#""pos":3"#));
+    }
+}
@@ -0,0 +1,153 @@
+use crossbeam_channel::Receiver;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TriggerEvent {
+    Start,
+    Step,
+    /// A printable key "typed" by the presenter. `erase` is set when the
+    /// backend could not swallow the key, so it reached the target and must be
+    /// deleted before the scripted actions are injected.
+    AirKey { erase: bool },
+    Back,
+    PauseToggle,
+    Pause,
+    Resume,
+    Seek(usize),
+    GotoMarker(String),
+    /// Takes a path of the branch at the playhead, by name or 1-based number.
+    Choose(String),
+    /// Jumps over the rest of the optional section under the playhead.
+    Skip,
+    Exit,
+}
+
+/// An event and the time its source produced it.
+pub type Stamped = (TriggerEvent, Instant);
+
+/// Anything that produces `TriggerEvent`s for the playback controller.
+///
+/// Events carry the time they were produced, so the controller can measure
+/// how long they queued. `None` from a blocking receive means the source is
+/// closed and playback should stop; with a timeout it means nothing arrived
+/// in time, or the source closed, which `is_closed` tells apart.
+pub trait TriggerSource {
+    /// Waits up to `timeout`, or until an event arrives with `None`.
+    fn recv_stamped(&self, timeout: Option<Duration>) -> Option<Stamped>;
+
+    /// Whether the source will never produce another event.
+    fn is_closed(&self) -> bool { false }
+
+    /// Drops the stamp; only tests care about the bare event.
+    #[cfg(test)]
+    fn recv_timeout(&self, timeout: Duration) -> Option<TriggerEvent> {
+        self.recv_stamped(Some(timeout)).map(|(ev, _)| ev)
+    }
+}
+
+/// Receiving end of a channel whose producers stamp events as they send
+/// them. Remembers when every producer has gone.
+pub struct StampedRx {
+    rx: Receiver<Stamped>,
+    closed: AtomicBool,
+}
+
+impl StampedRx {
+    pub fn new(rx: Receiver<Stamped>) -> Self {
+        Self { rx, closed: AtomicBool::new(false) }
+    }
+
+    pub fn recv(&self, timeout: Option<Duration>) -> Option<Stamped> {
+        let received = match timeout {
+            Some(t) => self.rx.recv_timeout(t).map_err(|e| e.is_disconnected()),
+            None => self.rx.recv().map_err(|_| true),
+        };
+        match received {
+            Ok(ev) => Some(ev),
+            Err(disconnected) => {
+                if disconnected {
+                    self.closed.store(true, Ordering::Relaxed);
+                }
+                None
+            }
+        }
+    }
+
+    pub fn is_closed(&self) -> bool { self.closed.load(Ordering::Relaxed) }
+}
+
+/// Merges several sources. The first open one is waited on in short slices,
+/// the others are only checked in between, so the interactive one should
+/// come first. It closes once all of them have.
+pub struct MultiTrigger {
+    sources: Vec<Box<dyn TriggerSource>>,
+}
+
+const SLICE: Duration = Duration::from_millis(5);
+
+impl MultiTrigger {
+    pub fn new(primary: Box<dyn TriggerSource>) -> Self {
+        Self { sources: vec![primary] }
+    }
+
+    pub fn push(&mut self, src: Box<dyn TriggerSource>) {
+        self.sources.push(src);
+    }
+
+    fn poll_once(&self, wait: Duration) -> Option<Stamped> {
+        let mut open = self.sources.iter().filter(|src| !src.is_closed());
+        let first = open.next()?;
+        if let Some(ev) = first.recv_stamped(Some(wait)) {
+            return Some(ev);
+        }
+        open.find_map(|src| src.recv_stamped(Some(Duration::ZERO)))
+    }
+}
+
+impl TriggerSource for MultiTrigger {
+    fn recv_stamped(&self, timeout: Option<Duration>) -> Option<Stamped> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let left = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if let Some(ev) = self.poll_once(left.map_or(SLICE, |l| l.min(SLICE))) {
+                return Some(ev);
+            }
+            if self.is_closed() || left.is_some_and(|l| l.is_zero()) {
+                return None;
+            }
+        }
+    }
+
+    fn is_closed(&self) -> bool { self.sources.iter().all(|src| src.is_closed()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl TriggerSource for StampedRx {
+        fn recv_stamped(&self, timeout: Option<Duration>) -> Option<Stamped> { self.recv(timeout) }
+        fn is_closed(&self) -> bool { StampedRx::is_closed(self) }
+    }
+
+    #[test]
+    fn multi_trigger_closes_once_every_source_has() {
+        let (tx1, rx1) = crossbeam_channel::unbounded();
+        let (tx2, rx2) = crossbeam_channel::unbounded();
+        let mut multi = MultiTrigger::new(Box::new(StampedRx::new(rx1)));
+        multi.push(Box::new(StampedRx::new(rx2)));
+        tx1.send((TriggerEvent::Step, Instant::now())).unwrap();
+        drop(tx1);
+        assert_eq!(multi.recv_timeout(Duration::from_millis(50)), Some(TriggerEvent::Step));
+        assert_eq!(multi.recv_timeout(Duration::from_millis(20)), None);
+        assert!(!multi.is_closed());
+
+        // the other source still delivers until it closes too
+        tx2.send((TriggerEvent::Back, Instant::now())).unwrap();
+        drop(tx2);
+        assert_eq!(multi.recv_stamped(None).map(|(ev, _)| ev), Some(TriggerEvent::Back));
+        assert_eq!(multi.recv_stamped(None), None);
+        assert!(multi.is_closed());
+    }
+}
@@ -1,10 +1,7 @@
 use tracing_subscriber::{fmt, EnvFilter};
 
-pub fn init() {
-    let filter = std::env::var("MONKEY_TYPER_LOG")
-        .or_else(|_| std::env::var("RUST_LOG"))
-        .unwrap_or_else(|_| "info".to_string());
-
+/// Installs the subscriber; `filter` is the resolved `log_level` setting.
+pub fn init(filter: &str) {
     let env_filter = EnvFilter::new(filter);
 
     fmt()
@@ -12,4 +9,4 @@ pub fn init() {
         .with_target(false)
         .without_time()
         .init();
-}
\ No newline at end of file
+}
@@ -4,21 +4,50 @@ mod logging;
 mod prelude;
 
 mod format {
+    pub mod actions;
+    pub mod asciinema;
+    pub mod include;
+    pub mod migrate;
+    pub mod stream;
+    pub mod template;
     pub mod tks_json;
+    pub mod validate;
 }
 mod engine {
+    pub mod controller;
+    #[allow(clippy::module_inception)]
     pub mod engine;
+    pub mod persist;
     pub mod playhead;
+    pub mod shadow;
+    pub mod stats;
+    pub mod status;
 }
 mod io {
+    #[cfg(unix)]
+    pub mod control;
+    #[cfg(target_os = "linux")]
+    pub mod device;
     pub mod keyboard;
+    pub mod remote;
+    pub mod trigger;
+}
+mod ui {
+    pub mod tui;
 }
 mod output {
+    pub mod editor;
+    pub mod export;
+    pub mod humanize;
     pub mod injector;
+    pub mod nvim;
     pub mod os_inject;
+    pub mod reset;
+    pub mod router;
+    pub mod tmux;
 }
 
-use crate::{output::injector::OutputInjector, prelude::*};
+use crate::prelude::*;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -35,22 +64,102 @@ struct Cli {
     #[arg(global = true, long = "global", default_value_t = false)]
     global: bool,
 
-    #[arg(
-        global = true,
-        long = "step-key",
-        alias = "trigger",
-        default_value = "right"
-    )]
-    step_key: String,
+    #[arg(global = true, long = "step-key", alias = "trigger")]
+    step_key: Option<String>,
+
+    #[arg(global = true, long = "pause-key")]
+    pause_key: Option<String>,
+
+    #[arg(global = true, long = "start-key")]
+    start_key: Option<String>,
+
+    #[arg(global = true, long = "exit-key")]
+    exit_key: Option<String>,
+
+    /// Keys that advance playback when air-typing: printable, none, or letters,digits,punct,space
+    #[arg(global = true, long = "air-keys")]
+    air_keys: Option<String>,
+
+    /// Scripted actions played per air-typed key
+    #[arg(global = true, long = "actions-per-key")]
+    actions_per_key: Option<usize>,
+
+    /// Output backend: os or humanize
+    #[arg(global = true, long = "backend")]
+    backend: Option<String>,
+
+    /// Target editor profile: plain or autoindent
+    #[arg(global = true, long = "editor")]
+    editor: Option<String>,
+
+    /// Play the script unattended, over and over (kiosk mode)
+    #[arg(global = true, long = "loop", default_value_t = false)]
+    looping: bool,
+
+    /// Seconds to hold the finished text before starting over in --loop
+    #[arg(global = true, long = "loop-wait", default_value_t = 5.0)]
+    loop_wait: f64,
+
+    /// How --loop resets the target: erase, or key combos like "ctrl+a backspace"
+    #[arg(global = true, long = "reset")]
+    reset: Option<String>,
+
+    /// Log filter, e.g. info or monkey_typer=debug
+    #[arg(global = true, long = "log-level")]
+    log_level: Option<String>,
+
+    /// Full-screen presenter view with progress, preview and notes
+    #[arg(global = true, long = "tui", default_value_t = false)]
+    tui: bool,
 
-    #[arg(global = true, long = "pause-key", default_value = "ctrl+p")]
-    pause_key: String,
+    /// Write per-step latency percentiles as JSON here on exit
+    #[arg(global = true, long = "latency-report")]
+    latency_report: Option<PathBuf>,
 
-    #[arg(global = true, long = "start-key", default_value = "ctrl+s")]
-    start_key: String,
+    /// Continue from the playhead saved by the last run of this script
+    #[arg(global = true, long = "resume", default_value_t = false)]
+    resume: bool,
 
-    #[arg(global = true, long = "exit-key", default_value = "ctrl+q")]
-    exit_key: String,
+    /// Save the playhead here; implied by --resume (default: <file>.state)
+    #[arg(global = true, long = "state-file")]
+    state_file: Option<PathBuf>,
+
+    /// Type runs of characters with a human rhythm instead of all at once (--backend humanize)
+    #[arg(global = true, long = "humanize", default_value_t = false)]
+    humanize: bool,
+
+    /// Seed for the humanized rhythm and typos
+    #[arg(global = true, long = "seed", default_value_t = 1)]
+    seed: u64,
+
+    /// Humanized typing speed in characters per second [default: 12]
+    #[arg(global = true, long = "cps")]
+    cps: Option<f64>,
+
+    /// Probability of a corrected typo per character when humanizing
+    #[arg(global = true, long = "typo-rate", default_value_t = 0.0)]
+    typo_rate: f64,
+
+    /// Value for {{key}} placeholders in the script (repeatable)
+    #[arg(global = true, long = "set", value_name = "KEY=VALUE")]
+    set: Vec<String>,
+
+    /// Bind a clicker/pedal: name=<name> or id=<vendor>:<product>[,<key>=<step|back|pause>...]
+    #[arg(global = true, long = "device")]
+    devices: Vec<String>,
+
+    /// Accept remote commands over HTTP on this address (e.g. 0.0.0.0:7878)
+    #[arg(global = true, long = "remote")]
+    remote: Option<String>,
+
+    /// Shared secret remote clients must present
+    #[arg(global = true, long = "remote-token")]
+    remote_token: Option<String>,
+
+    /// Control socket path (default: $XDG_RUNTIME_DIR/monkey-typer.sock, or
+    /// monkey-typer-<pid>.sock beside it when another instance holds that)
+    #[arg(global = true, long = "socket")]
+    socket: Option<PathBuf>,
 
     #[command(subcommand)]
     command: Commands,
@@ -59,148 +168,429 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     Livecli,
+    /// Send a command to a running livecli (step [N], pause, resume, goto <marker>, choose <path>, skip, status)
+    Ctl {
+        #[arg(required = true, num_args = 1..)]
+        command: Vec<String>,
+    },
+    /// Validate scripts and report problems as file:line:column
+    #[command(alias = "lint")]
+    Check {
+        /// Scripts to check (default: --file)
+        files: Vec<PathBuf>,
+    },
+    /// Rewrite scripts in place to the current format version
+    Migrate {
+        /// Scripts to migrate (default: --file)
+        files: Vec<PathBuf>,
+    },
+    /// Render --file to an asciinema .cast or animated .svg, typed at --cps with --seed
+    Export {
+        /// Output file; the format follows its extension (.cast or .svg)
+        output: PathBuf,
+        #[arg(long = "width", default_value_t = 80)]
+        width: u16,
+        #[arg(long = "height", default_value_t = 24)]
+        height: u16,
+    },
+    /// Convert a recording from another tool into a .tks.json script
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
+    /// Inspect the configuration files
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print the effective configuration and where each value comes from
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+enum ImportSource {
+    /// asciinema v2 .cast recording (input events, or typing echoed in the output)
+    Asciinema {
+        input: PathBuf,
+        /// Where to write the script, replacing it if it exists (default:
+        /// <input>.tks.json, which must not exist yet)
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
 }
 
 fn main() -> Result<()> {
-    logging::init();
     let cli = Cli::parse();
+    // only playback reads settings, so a broken config cannot stop the tools
+    let layers = match cli.command {
+        Commands::Livecli | Commands::Config { .. } => config_layers(&cli)?,
+        _ => vec![
+            config::Layer { source: "cli".to_string(), config: cli_flags(&cli) },
+            config::Layer { source: "env".to_string(), config: config::AppConfig::log_from_env() },
+        ],
+    };
+    logging::init(config::AppConfig::resolve(&layers).log_level());
     match cli.command {
-        Commands::Livecli => run_live_cli(cli)?,
+        Commands::Livecli => run_live_cli(cli, layers)?,
+        Commands::Config { action: ConfigAction::Show } => run_config_show(&cli, layers)?,
+        Commands::Ctl { ref command } => run_ctl(&cli, command)?,
+        Commands::Check { ref files } => run_check(&cli, files)?,
+        Commands::Migrate { ref files } => run_migrate(&cli, files)?,
+        Commands::Import { ref source } => run_import(source)?,
+        Commands::Export { ref output, width, height } => run_export(&cli, output, width, height)?,
+    }
+    Ok(())
+}
+
+/// Configuration sources, highest precedence first: CLI, environment,
+/// project files, user file. A loaded script's `meta` goes below the CLI.
+fn config_layers(cli: &Cli) -> Result<Vec<config::Layer>> {
+    use config::{AppConfig, Layer};
+    let mut layers = vec![
+        Layer { source: "cli".to_string(), config: cli_flags(cli) },
+        Layer { source: "env".to_string(), config: AppConfig::from_env()? },
+    ];
+    layers.extend(config::discover(&std::env::current_dir()?)?);
+    Ok(layers)
+}
+
+fn cli_flags(cli: &Cli) -> config::AppConfig {
+    config::AppConfig {
+        step_key: cli.step_key.clone(),
+        pause_key: cli.pause_key.clone(),
+        start_key: cli.start_key.clone(),
+        exit_key: cli.exit_key.clone(),
+        air_keys: cli.air_keys.clone(),
+        actions_per_key: cli.actions_per_key,
+        backend: cli.backend.clone().or_else(|| cli.humanize.then(|| "humanize".to_string())),
+        editor: cli.editor.clone(),
+        reset: cli.reset.clone(),
+        log_level: cli.log_level.clone(),
+        targets: Default::default(),
+    }
+}
+
+fn add_script_layer(layers: &mut Vec<config::Layer>, path: &std::path::Path, meta: &format::tks_json::TksMeta) {
+    let source = format!("{} meta", path.display());
+    layers.insert(1, config::Layer { source, config: meta.settings() });
+}
+
+/// Humanized speed: `--cps`, then the script's `meta`, then the default.
+fn humanize_config(cli: &Cli, meta: &format::tks_json::TksMeta) -> output::humanize::HumanizeConfig {
+    let defaults = output::humanize::HumanizeConfig::default();
+    let cps = cli.cps.or(meta.cps).unwrap_or(defaults.cps);
+    output::humanize::HumanizeConfig { seed: cli.seed, cps, typo_rate: cli.typo_rate, ..defaults }
+}
+
+/// Injector for one output target: its backend, humanized if configured.
+fn output_injector(
+    cli: &Cli,
+    cfg: &config::AppConfig,
+    meta: &format::tks_json::TksMeta,
+    target: &config::TargetConfig,
+) -> Result<Box<dyn output::injector::OutputInjector>> {
+    use output::editor::EditorProfile;
+    use output::{humanize::Humanizer, injector::OutputInjector, nvim::NvimInjector, os_inject::OsInjector, tmux::TmuxInjector};
+
+    let backend: Box<dyn OutputInjector> = match (&target.nvim, &target.tmux) {
+        (Some(server), _) => Box::new(NvimInjector::new(server)),
+        (None, Some(pane)) => Box::new(TmuxInjector::new(pane)),
+        (None, None) => Box::new(OsInjector::new()),
+    };
+    let injector: Box<dyn OutputInjector> = match cfg.backend() {
+        "humanize" => Box::new(Humanizer::new(backend, humanize_config(cli, meta))),
+        "os" => backend,
+        other => anyhow::bail!("unknown backend {other:?} (expected os or humanize)"),
+    };
+    let profile: EditorProfile = target.editor.as_deref().unwrap_or(cfg.editor()).parse()?;
+    Ok(profile.wrap(injector))
+}
+
+/// Script title for displays: `meta.title`, or the file name.
+fn script_title(path: &std::path::Path, meta: &format::tks_json::TksMeta) -> String {
+    meta.title.clone().unwrap_or_else(|| {
+        path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned())
+    })
+}
+
+fn run_config_show(cli: &Cli, mut layers: Vec<config::Layer>) -> Result<()> {
+    if let Some(path) = &cli.file {
+        add_script_layer(&mut layers, path, &format::tks_json::Sequence::load(path, &format::template::Vars::new(&cli.set)?)?.meta);
     }
+    print!("{}", config::show(&layers));
     Ok(())
 }
 
-fn run_live_cli(cli: Cli) -> Result<()> {
-    use engine::engine::Engine;
-    use output::os_inject::OsInjector;
+fn run_live_cli(cli: Cli, mut layers: Vec<config::Layer>) -> Result<()> {
+    use engine::{controller::Controller, engine::Engine, persist::StateFile};
+    use io::{keyboard, remote::RemoteTrigger, trigger::{MultiTrigger, TriggerSource}};
+    use output::router::Router;
 
-    let path = cli.file.expect("specify .tks.json");
-    let bytes = std::fs::read(path)?;
-    let seq = format::tks_json::Sequence::from_bytes(&bytes)?;
+    let path = cli.file.clone().expect("specify .tks.json");
+    let seq = format::tks_json::Sequence::load(&path, &format::template::Vars::new(&cli.set)?)?;
+    let meta = seq.meta.clone();
+    add_script_layer(&mut layers, &path, &meta);
+    let cfg = config::AppConfig::resolve(&layers);
+    let title = script_title(&path, &meta);
+    match (&meta.title, &meta.author) {
+        (Some(_), Some(author)) => println!("{title} by {author}"),
+        (Some(_), None) => println!("{title}"),
+        _ => {}
+    }
 
+    // the playhead is only saved when asked for, so plain runs leave no files
+    let state = (cli.resume || cli.state_file.is_some()).then(|| {
+        let state_path = cli.state_file.clone().unwrap_or_else(|| StateFile::default_path(&path));
+        StateFile::new(state_path, engine::persist::sequence_hash(&seq))
+    });
     let mut engine = Engine::new(seq.actions, seq.markers);
-    let mut injector = OsInjector::new();
-    let mut lat_samples: Vec<f64> = Vec::new();
-    let keys = io::keyboard::Hotkeys::from_strings(
-        &cli.step_key,
-        &cli.pause_key,
-        &cli.start_key,
-        &cli.exit_key,
-    );
+    engine.notes = seq.notes;
+    engine.optional = seq.optional;
+    engine.branches = seq.branches;
+    engine.targets = seq.targets;
+    if let Some(state) = state.as_ref().filter(|_| cli.resume) {
+        let (pos, route) = state.load()?;
+        engine.restore(pos, &route);
+        println!("Resuming at {}/{} ({})", pos, engine.head.end, state.path().display());
+    }
+    let mut injector = output_injector(&cli, &cfg, &meta, &config::TargetConfig::default())?;
+    let mut names: Vec<&str> = engine.targets.iter().map(|(_, t)| t.as_str()).filter(|t| !t.is_empty()).collect();
+    names.sort_unstable();
+    names.dedup();
+    if !names.is_empty() {
+        let mut router = Router::new(injector);
+        for name in names {
+            let target = cfg.targets.get(name).ok_or_else(|| {
+                anyhow::anyhow!("{}: output target {name:?} is not defined (add [targets.{name}] to the config)", path.display())
+            })?;
+            println!("  Target {name}: {}", target.describe());
+            router.add(name, output_injector(&cli, &cfg, &meta, target)?);
+        }
+        injector = Box::new(router);
+    }
+    let mut ctl = Controller::new(engine, injector);
+    ctl.state = state;
+    let keys = keyboard::Hotkeys::from_strings(
+        cfg.step_key(),
+        cfg.pause_key(),
+        cfg.start_key(),
+        cfg.exit_key(),
+    )
+    .with_air_keys(cfg.air_keys())?
+    .with_devices(&cli.devices)?;
+    ctl.actions_per_key = cfg.actions_per_key();
+
+    let remote = match &cli.remote {
+        Some(addr) => Some(RemoteTrigger::start(addr, cli.remote_token.clone(), ctl.hub.clone())?),
+        None => None,
+    };
 
-    let mut started = false;
+    let devices = open_devices(&keys)?;
+    let (mode, keyboard): (&str, Box<dyn TriggerSource>) = if cli.global {
+        ("GLOBAL", Box::new(keyboard::GlobalTrigger::new(keys.clone())))
+    } else {
+        ("LOCAL", Box::new(keyboard::LocalTrigger::new(keys.clone())?))
+    };
+    println!(
+        "Live CLI ({})\n  Start={:?} / Step={:?} + air keys ({}) / Pause={:?} / Exit={:?}",
+        mode, keys.start, keys.step, cfg.air_keys(), keys.pause, keys.exit
+    );
+    let mut src = MultiTrigger::new(keyboard);
+    if let Some(devices) = devices {
+        println!("  Devices: {}", keys.devices.len());
+        src.push(devices);
+    }
+    if let Some(remote) = remote {
+        println!("  Remote: http://{}", remote.addr());
+        src.push(Box::new(remote));
+    }
+    #[cfg(unix)]
+    {
+        let control = match &cli.socket {
+            Some(path) => io::control::ControlSocket::bind(path, ctl.hub.clone())?,
+            None => io::control::ControlSocket::bind_default(ctl.hub.clone())?,
+        };
+        println!("  Control: {}", control.path().display());
+        src.push(Box::new(control));
+    }
+    if cli.looping {
+        let interval = match cfg.backend() {
+            // the humanizer paces itself
+            "humanize" => std::time::Duration::ZERO,
+            _ => std::time::Duration::from_secs_f64(1.0 / humanize_config(&cli, &meta).cps),
+        };
+        let opts = engine::controller::LoopOptions {
+            interval,
+            wait: std::time::Duration::from_secs_f64(cli.loop_wait.max(0.0)),
+            reset: cfg.reset().parse()?,
+        };
+        println!("  Loop: {:.1}s between rounds, reset by {}", cli.loop_wait, cfg.reset());
+        ctl.state = None;
+        ctl.run_loop(&src, &opts)?;
+    } else if cli.tui {
+        let mut tui = ui::tui::Tui::enter()?;
+        ctl.quiet = true;
+        ctl.run_with(&src, |c| tui.draw(&ui::tui::Frame::capture(&title, c)))?;
+    } else {
+        ctl.run(&src)?;
+    }
+    drop(src);
 
-    if cli.global {
+    if let Some(report) = ctl.stats.report() {
+        let t = &report.total;
         println!(
-            "Live CLI (GLOBAL)\n  Start={:?} / Step={:?} + [a-z] / Pause=Ctrl+P / Exit=Ctrl+Q",
-            keys.start, keys.step
+            "Latency per step: p50 {:.2} / p95 {:.2} / p99 {:.2} / max {:.2} ms (n={}, jitter {:.2} ms)",
+            t.p50, t.p95, t.p99, t.max, t.count, t.jitter
         );
-        let trig = io::keyboard::GlobalTrigger::new(keys);
-
-        loop {
-            if let Some(ev) = trig.try_recv() {
-                use io::keyboard::TriggerEvent::*;
-                match ev {
-                    Start => {
-                        if !started {
-                            started = true;
-                            println!("[Started]");
-                        }
-                    }
-                    Step { from_alpha } => {
-                        if !started {
-                            continue;
-                        }
-                        if from_alpha {
-                            injector.backspace()?;
-                            std::thread::sleep(std::time::Duration::from_millis(1));
-                        }
-                        let t0 = std::time::Instant::now();
-                        engine.step(&mut injector)?;
-                        lat_samples.push(t0.elapsed().as_secs_f64() * 1000.0);
-                        if engine.head.is_eof() {
-                            println!("\n[Done]");
-                            break;
-                        }
-                    }
-                    PauseToggle => {
-                        engine.toggle_pause();
-                        println!(
-                            "\r[{}]   ",
-                            if engine.head.paused {
-                                "Paused"
-                            } else {
-                                "Running"
-                            }
-                        );
-                    }
-                    Exit => {
-                        println!("\n[Exit]");
-                        break;
-                    }
+        if let Some(out) = &cli.latency_report {
+            std::fs::write(out, serde_json::to_vec_pretty(&report)?)?;
+            println!("Latency report written to {}", out.display());
+        }
+    }
+    Ok(())
+}
+
+/// Positional scripts, or `--file` when none are given.
+fn script_args(cli: &Cli, files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    match (files.is_empty(), &cli.file) {
+        (true, Some(f)) => Ok(vec![f.clone()]),
+        (true, None) => anyhow::bail!("specify scripts or --file"),
+        (false, _) => Ok(files.to_vec()),
+    }
+}
+
+fn run_check(cli: &Cli, files: &[PathBuf]) -> Result<()> {
+    let files = script_args(cli, files)?;
+    let vars = format::template::Vars::new(&cli.set)?;
+    let mut problems = 0;
+    for path in &files {
+        let name = path.display().to_string();
+        let bytes = std::fs::read(path).map_err(|e| anyhow::anyhow!("{name}: {e}"))?;
+        match format::validate::validate_at(&bytes, path, &vars) {
+            Ok(file) => {
+                let text = format::tks_json::Sequence::flatten(&file).final_text();
+                println!("{name}: ok (final_hash {})", engine::persist::script_hash(text.as_bytes()));
+            }
+            Err(diags) => {
+                problems += diags.len();
+                for d in diags {
+                    println!("{}", d.into_error(&name));
                 }
             }
-            std::thread::sleep(std::time::Duration::from_millis(1));
         }
-    } else {
-        use crossterm::terminal;
-        terminal::enable_raw_mode()?;
-        println!(
-            "Live CLI (LOCAL)\n  Start={:?} / Step={:?} + [a-z] / Pause=Ctrl+P / Exit=Ctrl+Q",
-            keys.start, keys.step
-        );
+    }
+    if problems > 0 {
+        println!("{} problem(s) found", problems);
+        std::process::exit(1);
+    }
+    Ok(())
+}
 
-        loop {
-            if let Some(ev) = io::keyboard::poll_trigger_local(1, &keys) {
-                use io::keyboard::TriggerEvent::*;
-                match ev {
-                    Start => {
-                        if !started {
-                            started = true;
-                            println!("[Started]");
-                        }
-                    }
-                    Step { from_alpha } => {
-                        if !started {
-                            continue;
-                        }
-                        if from_alpha {
-                            injector.backspace()?;
-                            std::thread::sleep(std::time::Duration::from_millis(1));
-                        }
-                        let t0 = std::time::Instant::now();
-                        engine.step(&mut injector)?;
-                        lat_samples.push(t0.elapsed().as_secs_f64() * 1000.0);
-                        if engine.head.is_eof() {
-                            println!("\n[Done]");
-                            break;
-                        }
-                    }
-                    PauseToggle => {
-                        engine.toggle_pause();
-                        println!(
-                            "\r[{}]   ",
-                            if engine.head.paused {
-                                "Paused"
-                            } else {
-                                "Running"
-                            }
-                        );
-                    }
-                    Exit => {
-                        println!("\n[Exit]");
-                        break;
-                    }
-                }
+fn run_migrate(cli: &Cli, files: &[PathBuf]) -> Result<()> {
+    use format::migrate;
+    for path in script_args(cli, files)? {
+        let name = path.display().to_string();
+        let bytes = std::fs::read(&path).map_err(|e| anyhow::anyhow!("{name}: {e}"))?;
+        let mut doc: serde_json::Value = serde_json::from_slice(&bytes)
+            .map_err(|e| anyhow::anyhow!("{name}:{}:{}: {e}", e.line(), e.column()))?;
+        let from = migrate::upgrade(&mut doc).map_err(|e| anyhow::anyhow!("{name}: {e}"))?;
+        if from == migrate::CURRENT {
+            println!("{name}: already version {}", migrate::CURRENT);
+            continue;
+        }
+        // validates the upgraded script, with locations in the original text
+        let file = format::tks_json::TksFile::parse(&name, &bytes)?;
+        std::fs::write(&path, file.to_pretty())?;
+        println!("{name}: version {from} -> {}", migrate::CURRENT);
+    }
+    Ok(())
+}
+
+fn run_export(cli: &Cli, output: &std::path::Path, width: u16, height: u16) -> Result<()> {
+    use output::export;
+    let path = cli.file.clone().expect("specify .tks.json");
+    let seq = format::tks_json::Sequence::load(&path, &format::template::Vars::new(&cli.set)?)?;
+    if !seq.targets.is_empty() {
+        warn!("{}: only the default output target is exported", path.display());
+    }
+    let frames = export::autoplay(&seq, humanize_config(cli, &seq.meta));
+    let title = script_title(&path, &seq.meta);
+    let text = match output.extension().and_then(|e| e.to_str()) {
+        Some("cast") => export::to_cast(&frames, &title, width, height),
+        Some("svg") => export::to_svg(&frames, width, height),
+        _ => anyhow::bail!("{}: unknown export format (use .cast or .svg)", output.display()),
+    };
+    std::fs::write(output, text)?;
+    let secs = frames.last().map_or(0.0, |f| match f {
+        export::Frame::Key { t, .. } | export::Frame::Marker { t, .. } => *t,
+    });
+    println!("{} -> {} ({:.1}s)", path.display(), output.display(), secs);
+    Ok(())
+}
+
+fn run_import(source: &ImportSource) -> Result<()> {
+    let ImportSource::Asciinema { input, output } = source;
+    let name = input.display().to_string();
+    let reader = std::io::BufReader::new(
+        std::fs::File::open(input).map_err(|e| anyhow::anyhow!("{name}: {e}"))?,
+    );
+    let file = format::asciinema::import(reader).map_err(|e| anyhow::anyhow!("{name}: {e}"))?;
+    // an explicit -o may overwrite; the default never clobbers a script
+    let output = match output {
+        Some(path) => path.clone(),
+        None => {
+            let path = input.with_extension("tks.json");
+            if path.exists() {
+                anyhow::bail!("{} already exists; pass -o to overwrite it or write elsewhere", path.display());
             }
-            std::thread::sleep(std::time::Duration::from_millis(0));
+            path
+        }
+    };
+    let text = file.to_pretty();
+    if let Err(diags) = format::validate::validate(text.as_bytes()) {
+        for d in diags {
+            eprintln!("{}", d.into_error(&output.display().to_string()));
         }
-        crossterm::terminal::disable_raw_mode()?;
+        anyhow::bail!("{name}: imported script is invalid; not written");
+    }
+    std::fs::write(&output, text)?;
+    let events = file.tracks.iter().map(|t| t.sequence.len()).sum::<usize>();
+    println!("{name}: {} events -> {}", events, output.display());
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn open_devices(keys: &io::keyboard::Hotkeys) -> Result<Option<Box<dyn io::trigger::TriggerSource>>> {
+    if keys.devices.is_empty() {
+        return Ok(None);
     }
+    Ok(Some(Box::new(io::device::DeviceTrigger::open(&keys.devices)?)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn open_devices(keys: &io::keyboard::Hotkeys) -> Result<Option<Box<dyn io::trigger::TriggerSource>>> {
+    if !keys.devices.is_empty() {
+        anyhow::bail!("--device is only supported on Linux");
+    }
+    Ok(None)
+}
 
-    if !lat_samples.is_empty() {
-        let avg = lat_samples.iter().copied().sum::<f64>() / (lat_samples.len() as f64);
-        println!("Latency(avg per step): {:.2} ms", avg);
+#[cfg(unix)]
+fn run_ctl(cli: &Cli, command: &[String]) -> Result<()> {
+    let path = cli.socket.clone().unwrap_or_else(io::control::default_socket_path);
+    let reply = io::control::send(&path, &command.join(" "))?;
+    println!("{}", reply);
+    if reply.starts_with("error:") {
+        std::process::exit(1);
     }
     Ok(())
 }
+
+#[cfg(not(unix))]
+fn run_ctl(_cli: &Cli, _command: &[String]) -> Result<()> {
+    anyhow::bail!("ctl is only available on Unix platforms")
+}
@@ -0,0 +1,137 @@
+use super::injector::OutputInjector;
+use super::reset::KeyCombo;
+use crate::prelude::*;
+use std::str::FromStr;
+
+/// What the target editor does on its own while we type, and so what
+/// [`EditorProfile::wrap`] leaves out of the keys we send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EditorProfile {
+    /// Keys are sent exactly as scripted.
+    #[default]
+    Plain,
+    /// After Enter the editor indents the new line itself, so scripted
+    /// indentation at the start of a line is not sent.
+    AutoIndent,
+}
+
+impl EditorProfile {
+    pub fn wrap(self, inner: Box<dyn OutputInjector>) -> Box<dyn OutputInjector> {
+        match self {
+            Self::Plain => inner,
+            Self::AutoIndent => Box::new(AutoIndent::new(inner)),
+        }
+    }
+}
+
+/// Drops spaces and tabs typed at the start of a line, and the backspaces
+/// that would erase them. Backspacing over a line break is sent as is, so
+/// the editor decides what it removes. Pastes are typed, as the editor
+/// would indent them too.
+pub struct AutoIndent<I> {
+    inner: I,
+    /// Per line: indentation dropped so far, or `None` once text started.
+    lines: Vec<Option<usize>>,
+}
+
+impl<I: OutputInjector> AutoIndent<I> {
+    pub fn new(inner: I) -> Self { Self { inner, lines: vec![None] } }
+
+    fn line(&mut self) -> &mut Option<usize> { self.lines.last_mut().expect("always one line") }
+}
+
+impl<I: OutputInjector> OutputInjector for AutoIndent<I> {
+    fn send_char(&mut self, ch: char) -> Result<()> {
+        match (ch, self.line()) {
+            ('\n', _) => self.lines.push(Some(0)),
+            (' ' | '\t', Some(dropped)) => {
+                *dropped += 1;
+                return Ok(());
+            }
+            (_, line) => *line = None,
+        }
+        self.inner.send_char(ch)
+    }
+
+    fn backspace(&mut self) -> Result<()> {
+        let first = self.lines.len() == 1;
+        match self.line() {
+            Some(dropped) if *dropped > 0 => {
+                *dropped -= 1;
+                return Ok(());
+            }
+            Some(_) if !first => {
+                self.lines.pop();
+            }
+            // The line's text is unknown here; erasing it cannot bring
+            // back the start of the line.
+            _ => {}
+        }
+        self.inner.backspace()
+    }
+
+    fn combo(&mut self, combo: &KeyCombo) -> Result<()> {
+        self.lines = vec![None];
+        self.inner.combo(combo)
+    }
+
+    fn target(&mut self, name: &str) -> Result<()> { self.inner.target(name) }
+    fn name(&self) -> &'static str { self.inner.name() }
+}
+
+impl FromStr for EditorProfile {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "plain" | "none" => Ok(Self::Plain),
+            "autoindent" | "auto-indent" => Ok(Self::AutoIndent),
+            other => anyhow::bail!("unknown editor profile {other:?} (expected plain or autoindent)"),
+        }
+    }
+}
+
+impl std::fmt::Display for EditorProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Plain => "plain",
+            Self::AutoIndent => "autoindent",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_parse_and_print() {
+        assert_eq!("plain".parse::<EditorProfile>().unwrap(), EditorProfile::Plain);
+        assert_eq!(" Auto-Indent".parse::<EditorProfile>().unwrap().to_string(), "autoindent");
+        assert!("vim".parse::<EditorProfile>().is_err());
+    }
+
+    #[derive(Default)]
+    struct Buffer(String);
+
+    impl OutputInjector for Buffer {
+        fn send_char(&mut self, ch: char) -> Result<()> {
+            self.0.push(ch);
+            Ok(())
+        }
+        fn backspace(&mut self) -> Result<()> {
+            self.0.pop();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn autoindent_leaves_leading_indentation_to_the_editor() {
+        let mut out = AutoIndent::new(Buffer::default());
+        out.paste("fn f() {\n    let a = 1;\n  \t").unwrap();
+        (0..2).for_each(|_| out.backspace().unwrap());
+        out.paste("x\n    ").unwrap();
+        (0..5).for_each(|_| out.backspace().unwrap());
+        assert_eq!(out.inner.0, "fn f() {\nlet a = 1;\nx");
+    }
+}
@@ -0,0 +1,275 @@
+use super::humanize::{HumanizeConfig, TimingModel};
+use crate::engine::shadow::ShadowBuffer;
+use crate::format::tks_json::{Action, Sequence};
+use std::fmt::Write;
+
+/// Extra time spent at a marker, where the presenter would stop to talk.
+const MARKER_PAUSE: f64 = 1.5;
+
+/// SVG glyph metrics for a 14px monospace font.
+const CHAR_W: f64 = 8.4;
+const LINE_H: f64 = 18.0;
+const PAD: f64 = 12.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    /// An action applied at `t` seconds; `erased` is what a backspace removed.
+    Key { t: f64, act: Action, erased: Option<char> },
+    Marker { t: f64, name: String },
+}
+
+/// Plays `seq` through a shadow buffer on a virtual clock driven by the
+/// humanizer's timing model (typos included), so no display is needed.
+/// Branches take their default path, and only the default output target is
+/// recorded: actions sent to other targets happen on another screen.
+pub fn autoplay(seq: &Sequence, cfg: HumanizeConfig) -> Vec<Frame> {
+    let mut model = TimingModel::new(cfg);
+    let mut shadow = ShadowBuffer::new();
+    let mut frames = Vec::new();
+    let mut markers = seq.markers.iter().peekable();
+    let mut t = 0.0;
+    let key = |act: Action, t: f64, shadow: &mut ShadowBuffer, frames: &mut Vec<Frame>| {
+        let erased = match act {
+            Action::Backspace => shadow.text().chars().next_back(),
+            Action::Char(_) => None,
+        };
+        shadow.apply(act);
+        frames.push(Frame::Key { t, act, erased });
+    };
+    for (pos, act) in seq.actions.iter().enumerate() {
+        while let Some((at, name)) = markers.next_if(|(p, _)| *p <= pos) {
+            if seq.off_default(*at) {
+                continue;
+            }
+            frames.push(Frame::Marker { t, name: name.clone() });
+            t += MARKER_PAUSE;
+        }
+        if seq.off_default(pos) || !seq.target_at(pos).is_empty() {
+            continue;
+        }
+        match act {
+            Action::Char(ch) => {
+                let delay = model.next_delay(ch).as_secs_f64();
+                t += delay;
+                if let Some(wrong) = model.typo_for(ch) {
+                    key(Action::Char(wrong), t, &mut shadow, &mut frames);
+                    t += delay * 3.0;
+                    key(Action::Backspace, t, &mut shadow, &mut frames);
+                    t += delay;
+                }
+            }
+            Action::Backspace => t += model.next_delay('\u{8}').as_secs_f64(),
+        }
+        key(act, t, &mut shadow, &mut frames);
+    }
+    for (_, name) in markers {
+        frames.push(Frame::Marker { t, name: name.clone() });
+    }
+    frames
+}
+
+/// asciinema v2 recording of the frames.
+pub fn to_cast(frames: &[Frame], title: &str, width: u16, height: u16) -> String {
+    let header = serde_json::json!({ "version": 2, "width": width, "height": height, "title": title });
+    let mut out = format!("{header}\n");
+    let mut push = |t: f64, kind: &str, data: &str| {
+        let ev = serde_json::json!([(t * 1e6).round() / 1e6, kind, data]);
+        out += &format!("{ev}\n");
+    };
+    // width of every line so far, to find the column after erasing a newline
+    let mut lines = vec![0usize];
+    for frame in frames {
+        match frame {
+            Frame::Marker { t, name } => push(*t, "m", name),
+            Frame::Key { t, act: Action::Char('\n'), .. } => {
+                lines.push(0);
+                push(*t, "o", "\r\n");
+            }
+            Frame::Key { t, act: Action::Char(ch), .. } => {
+                *lines.last_mut().expect("never empty") += 1;
+                push(*t, "o", &ch.to_string());
+            }
+            Frame::Key { t, act: Action::Backspace, erased: Some('\n') } => {
+                lines.pop();
+                let col = lines.last().copied().unwrap_or(0) + 1;
+                push(*t, "o", &format!("\u{1b}[A\u{1b}[{col}G"));
+            }
+            Frame::Key { t, act: Action::Backspace, erased: Some(_) } => {
+                *lines.last_mut().expect("never empty") -= 1;
+                push(*t, "o", "\u{8} \u{8}");
+            }
+            Frame::Key { act: Action::Backspace, erased: None, .. } => {}
+        }
+    }
+    out
+}
+
+/// Self-contained animated SVG: every glyph is shown from the moment it is
+/// typed until it is erased, and the page scrolls once the text outgrows
+/// `height` lines. It plays once and holds the last frame.
+pub fn to_svg(frames: &[Frame], width: u16, height: u16) -> String {
+    struct Glyph {
+        ch: char,
+        row: usize,
+        col: usize,
+        from: f64,
+        to: Option<f64>,
+    }
+    let mut glyphs: Vec<Glyph> = Vec::new();
+    let mut live: Vec<usize> = Vec::new();
+    let (mut row, mut col) = (0usize, 0usize);
+    let mut scrolls: Vec<(f64, usize)> = vec![(0.0, 0)];
+    let mut cursor: Vec<(f64, usize, usize)> = vec![(0.0, 0, 0)];
+
+    for frame in frames {
+        let Frame::Key { t, act, .. } = frame else { continue };
+        match act {
+            Action::Char(ch) => {
+                live.push(glyphs.len());
+                glyphs.push(Glyph { ch: *ch, row, col, from: *t, to: None });
+                if *ch == '\n' {
+                    row += 1;
+                    col = 0;
+                } else {
+                    col += 1;
+                }
+            }
+            Action::Backspace => {
+                if let Some(i) = live.pop() {
+                    glyphs[i].to = Some(*t);
+                    row = glyphs[i].row;
+                    col = glyphs[i].col;
+                }
+            }
+        }
+        let top = (row + 1).saturating_sub(height as usize);
+        if scrolls.last().map(|s| s.1) != Some(top) {
+            scrolls.push((*t, top));
+        }
+        cursor.push((*t, row, col));
+    }
+
+    let w = PAD * 2.0 + CHAR_W * width as f64;
+    let h = PAD * 2.0 + LINE_H * height as f64;
+    let x = |col: usize| PAD + CHAR_W * col as f64;
+    let y = |row: usize| PAD + LINE_H * (row as f64 + 0.8);
+    let mut out = String::new();
+    let _ = writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#);
+    let _ = writeln!(out, r##"<rect width="100%" height="100%" rx="6" fill="#1e1e1e"/>"##);
+    let _ = writeln!(out, r##"<svg x="0" y="0" width="{w}" height="{h}"><g font-family="monospace" font-size="14" fill="#d4d4d4" xml:space="preserve">"##);
+    for (t, top) in &scrolls {
+        let _ = writeln!(
+            out,
+            r#"<set attributeName="transform" to="translate(0,{:.1})" begin="{:.3}s" fill="freeze"/>"#,
+            -(LINE_H * *top as f64),
+            t
+        );
+    }
+    for g in glyphs.iter().filter(|g| !g.ch.is_whitespace()) {
+        let mut ch = [0u8; 4];
+        let _ = write!(
+            out,
+            r#"<text x="{:.1}" y="{:.1}" visibility="hidden">{}<set attributeName="visibility" to="visible" begin="{:.3}s" fill="freeze"/>"#,
+            x(g.col),
+            y(g.row),
+            xml_escape(g.ch.encode_utf8(&mut ch)),
+            g.from
+        );
+        if let Some(to) = g.to {
+            let _ = write!(out, r#"<set attributeName="visibility" to="hidden" begin="{:.3}s" fill="freeze"/>"#, to);
+        }
+        out += "</text>\n";
+    }
+    let _ = write!(
+        out,
+        r##"<rect x="{:.1}" y="{:.1}" width="{CHAR_W}" height="{LINE_H}" fill="#d4d4d4" opacity="0.6">"##,
+        x(0),
+        PAD
+    );
+    for (t, row, col) in cursor.iter().skip(1) {
+        let _ = write!(
+            out,
+            r#"<set attributeName="x" to="{:.1}" begin="{t:.3}s" fill="freeze"/><set attributeName="y" to="{:.1}" begin="{t:.3}s" fill="freeze"/>"#,
+            x(*col),
+            PAD + LINE_H * *row as f64
+        );
+    }
+    out += "</rect>\n</g></svg>\n</svg>\n";
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seq() -> Sequence {
+        let mut seq = Sequence::default();
+        seq.actions.push_text("ab\n");
+        seq.actions.push_delete(2);
+        seq.actions.push_text("<c");
+        seq.markers.push((3, "nl".into()));
+        seq
+    }
+
+    #[test]
+    fn autoplay_is_timed_and_reproducible() {
+        let cfg = HumanizeConfig { seed: 4, ..Default::default() };
+        let frames = autoplay(&seq(), cfg.clone());
+        assert_eq!(frames, autoplay(&seq(), cfg));
+        let times: Vec<f64> = frames
+            .iter()
+            .map(|f| match f {
+                Frame::Key { t, .. } | Frame::Marker { t, .. } => *t,
+            })
+            .collect();
+        assert!(times.windows(2).all(|w| w[0] <= w[1]));
+        assert!(matches!(&frames[3], Frame::Marker { name, .. } if name == "nl"));
+        let mut text = ShadowBuffer::new();
+        for f in &frames {
+            if let Frame::Key { act, .. } = f {
+                text.apply(*act);
+            }
+        }
+        assert_eq!(text.text(), "a<c");
+    }
+
+    #[test]
+    fn only_the_default_target_is_recorded() {
+        let json = br#"{"version":"2","tracks":[{"id":"main","sequence":[
+            {"type":"Insert","text":"fn"},
+            {"type":"Target","name":"shell"},
+            {"type":"Insert","text":"ls"},
+            {"type":"Target","name":""},
+            {"type":"Insert","text":"()"}
+        ]}]}"#;
+        let seq = Sequence::from_bytes(json).unwrap();
+        let mut text = ShadowBuffer::new();
+        for f in autoplay(&seq, HumanizeConfig::default()) {
+            if let Frame::Key { act, .. } = f {
+                text.apply(act);
+            }
+        }
+        assert_eq!(text.text(), seq.final_text());
+    }
+
+    #[test]
+    fn cast_and_svg_render_the_evolving_text() {
+        let frames = autoplay(&seq(), HumanizeConfig::default());
+        let cast = to_cast(&frames, "demo", 80, 24);
+        let lines: Vec<serde_json::Value> = cast.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines[0]["version"], 2);
+        let data: Vec<&str> = lines[1..].iter().map(|ev| ev[2].as_str().unwrap()).collect();
+        assert_eq!(data, ["a", "b", "\r\n", "nl", "\u{1b}[A\u{1b}[3G", "\u{8} \u{8}", "<", "c"]);
+
+        let svg = to_svg(&frames, 80, 1);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(">&lt;<set"));
+        // 'b' is erased, the one-line window scrolls for the newline and back
+        assert_eq!(svg.matches(r#"to="hidden""#).count(), 1);
+        assert_eq!(svg.matches(r#"attributeName="transform""#).count(), 3);
+    }
+}
@@ -0,0 +1,285 @@
+use super::injector::OutputInjector;
+use super::reset::KeyCombo;
+use crate::format::tks_json::Action;
+use crate::prelude::*;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone)]
+pub struct HumanizeConfig {
+    pub seed: u64,
+    /// Average typing speed in characters per second.
+    pub cps: f64,
+    /// Relative spread of each delay, 0.0 = metronome.
+    pub jitter: f64,
+    /// Probability of hitting a neighbouring key first and correcting it.
+    pub typo_rate: f64,
+}
+
+impl Default for HumanizeConfig {
+    fn default() -> Self {
+        Self { seed: 1, cps: 12.0, jitter: 0.35, typo_rate: 0.0 }
+    }
+}
+
+const COMMON_BIGRAMS: &[&str] = &[
+    "th", "he", "in", "er", "an", "re", "on", "at", "en", "nd", "st", "es", "or", "te", "ed",
+    "of", "ti", "is", "it", "ar", "ng", "le", "se", "nt", "fn", "()", "{}", "::", "//",
+];
+
+const QWERTY_ROWS: &[&str] = &["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+/// Seeded, reproducible typing rhythm: inter-key delays plus occasional typos.
+pub struct TimingModel {
+    cfg: HumanizeConfig,
+    rng: SplitMix64,
+    prev: Option<char>,
+    burst_left: u32,
+}
+
+impl TimingModel {
+    pub fn new(cfg: HumanizeConfig) -> Self {
+        let rng = SplitMix64(cfg.seed);
+        Self { cfg, rng, prev: None, burst_left: 0 }
+    }
+
+    /// Delay to wait before typing `ch` after the previous character.
+    pub fn next_delay(&mut self, ch: char) -> Duration {
+        let base = 1.0 / self.cfg.cps.max(0.1);
+        let mut factor = match self.prev {
+            Some('\n') => 4.0,
+            Some('.' | ',' | ';' | ':' | '!' | '?') => 2.5,
+            Some(prev) if is_common_bigram(prev, ch) => 0.6,
+            _ => 1.0,
+        };
+
+        if self.burst_left > 0 {
+            self.burst_left -= 1;
+            factor *= 0.5;
+        } else if self.rng.next_f64() < 0.08 {
+            self.burst_left = 3 + (self.rng.next_u64() % 6) as u32;
+        }
+
+        let spread = self.cfg.jitter * (self.rng.next_f64() * 2.0 - 1.0);
+        self.prev = Some(ch);
+        Duration::from_secs_f64((base * factor * (1.0 + spread)).max(0.0))
+    }
+
+    /// A neighbouring key to hit by mistake before `ch`, if a typo happens.
+    pub fn typo_for(&mut self, ch: char) -> Option<char> {
+        if self.cfg.typo_rate <= 0.0 || self.rng.next_f64() >= self.cfg.typo_rate {
+            return None;
+        }
+        let lower = ch.to_ascii_lowercase();
+        let row = QWERTY_ROWS.iter().find(|r| r.contains(lower))?.as_bytes();
+        let i = row.iter().position(|&b| b as char == lower)?;
+        let j = match (i, self.rng.next_u64() % 2) {
+            (0, _) => 1,
+            (i, _) if i + 1 == row.len() => i - 1,
+            (i, 0) => i - 1,
+            (i, _) => i + 1,
+        };
+        let wrong = row[j] as char;
+        Some(if ch.is_ascii_uppercase() { wrong.to_ascii_uppercase() } else { wrong })
+    }
+}
+
+fn is_common_bigram(prev: char, ch: char) -> bool {
+    let pair: String = [prev, ch].iter().map(char::to_ascii_lowercase).collect();
+    COMMON_BIGRAMS.contains(&pair.as_str())
+}
+
+/// Injector decorator applying a `TimingModel` between consecutive keys.
+///
+/// The first key after an idle period goes out immediately, so a single step
+/// stays responsive; only runs of keys (multi-action steps, seeks, autoplay)
+/// get the human rhythm. Typos are corrected before `send_char` returns; one
+/// left behind by a failed correction is erased before anything else is
+/// sent, so the text matches the script either way.
+pub struct Humanizer<I: OutputInjector> {
+    inner: I,
+    model: TimingModel,
+    last: Option<Instant>,
+    /// Wrong keys sent and not yet erased.
+    typos: usize,
+}
+
+impl<I: OutputInjector> Humanizer<I> {
+    pub fn new(inner: I, cfg: HumanizeConfig) -> Self {
+        Self { inner, model: TimingModel::new(cfg), last: None, typos: 0 }
+    }
+
+    /// Erases typos a failed correction left in the target.
+    fn repair(&mut self) -> Result<()> {
+        while self.typos > 0 {
+            warn!("erasing an uncorrected typo");
+            self.inner.backspace()?;
+            self.typos -= 1;
+        }
+        Ok(())
+    }
+
+    fn pace(&mut self, delay: Duration) {
+        if let Some(last) = self.last {
+            let since = last.elapsed();
+            if since < delay {
+                std::thread::sleep(delay - since);
+            }
+        }
+    }
+
+    fn emit(&mut self, act: Action) -> Result<()> {
+        match act {
+            Action::Char(ch) => self.inner.send_char(ch)?,
+            Action::Backspace => self.inner.backspace()?,
+        }
+        self.last = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl<I: OutputInjector> OutputInjector for Humanizer<I> {
+    fn send_char(&mut self, ch: char) -> Result<()> {
+        self.repair()?;
+        let delay = self.model.next_delay(ch);
+        self.pace(delay);
+        if let Some(wrong) = self.model.typo_for(ch) {
+            debug!("typo {:?} for {:?}", wrong, ch);
+            self.emit(Action::Char(wrong))?;
+            self.typos += 1;
+            self.pace(delay * 3);
+            self.emit(Action::Backspace)?;
+            self.typos -= 1;
+            self.pace(delay);
+        }
+        self.emit(Action::Char(ch))
+    }
+
+    fn backspace(&mut self) -> Result<()> {
+        self.repair()?;
+        let delay = self.model.next_delay('\u{8}');
+        self.pace(delay);
+        self.emit(Action::Backspace)
+    }
+
+    /// Pastes are not performed by hand, so they skip the timing model.
+    fn paste(&mut self, text: &str) -> Result<()> {
+        self.repair()?;
+        self.inner.paste(text)?;
+        self.last = Some(Instant::now());
+        Ok(())
+    }
+
+    fn combo(&mut self, combo: &KeyCombo) -> Result<()> {
+        self.inner.combo(combo)?;
+        self.typos = 0;
+        Ok(())
+    }
+
+    fn target(&mut self, name: &str) -> Result<()> {
+        self.repair()?;
+        self.inner.target(name)
+    }
+
+    fn name(&self) -> &'static str { "humanize" }
+}
+
+/// Tiny deterministic PRNG, so a seed reproduces the same performance.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::engine::shadow::ShadowBuffer;
+
+    /// Records keys; fails the next `fail` backspaces.
+    #[derive(Default)]
+    struct Recorder {
+        keys: Vec<Action>,
+        fail: usize,
+    }
+
+    impl OutputInjector for Recorder {
+        fn send_char(&mut self, ch: char) -> Result<()> {
+            self.keys.push(Action::Char(ch));
+            Ok(())
+        }
+        fn backspace(&mut self) -> Result<()> {
+            if self.fail > 0 {
+                self.fail -= 1;
+                anyhow::bail!("backspace lost");
+            }
+            self.keys.push(Action::Backspace);
+            Ok(())
+        }
+    }
+
+    fn replayed(keys: &[Action]) -> String {
+        let mut replay = ShadowBuffer::new();
+        keys.iter().for_each(|act| replay.apply(*act));
+        replay.text().to_string()
+    }
+
+    fn fast(seed: u64, typo_rate: f64) -> HumanizeConfig {
+        HumanizeConfig { seed, cps: 1e6, jitter: 0.3, typo_rate }
+    }
+
+    #[test]
+    fn same_seed_same_rhythm() {
+        let text = "fn main() {\n    println!(\"hi\");\n}";
+        let delays = |seed| {
+            let mut m = TimingModel::new(HumanizeConfig { seed, ..Default::default() });
+            text.chars().map(|c| m.next_delay(c)).collect::<Vec<_>>()
+        };
+        assert_eq!(delays(7), delays(7));
+        assert_ne!(delays(7), delays(8));
+    }
+
+    #[test]
+    fn punctuation_and_newlines_slow_down() {
+        let delay = |prev, ch| {
+            let mut m = TimingModel::new(HumanizeConfig { jitter: 0.0, ..Default::default() });
+            m.prev = Some(prev);
+            m.next_delay(ch)
+        };
+        let plain = delay('x', 'q');
+        assert!(delay('.', 'q') > plain);
+        assert!(delay('\n', 'q') > delay('.', 'q'));
+        assert!(delay('t', 'h') < plain);
+    }
+
+    #[test]
+    fn typos_are_corrected_and_text_is_unchanged() {
+        let text = "Hello world, quick brown fox";
+        let mut h = Humanizer::new(Recorder::default(), fast(3, 0.5));
+        for ch in text.chars() {
+            h.send_char(ch).unwrap();
+        }
+        assert_eq!(replayed(&h.inner.keys), text);
+        assert!(h.inner.keys.len() > text.chars().count(), "expected some typos");
+    }
+
+    #[test]
+    fn a_typo_left_by_a_failed_correction_is_erased_next() {
+        let mut h = Humanizer::new(Recorder { fail: 1, ..Default::default() }, fast(3, 1.0));
+        assert!(h.send_char('a').is_err());
+        assert_eq!(h.inner.keys.len(), 1, "only the typo went out");
+        h.send_char('a').unwrap();
+        h.paste("bc").unwrap();
+        assert_eq!(replayed(&h.inner.keys), "abc");
+    }
+}
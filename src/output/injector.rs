@@ -1,6 +1,39 @@
+use super::reset::KeyCombo;
 use crate::prelude::*;
 
 pub trait OutputInjector {
     fn send_char(&mut self, ch: char) -> Result<()>;
     fn backspace(&mut self) -> Result<()>;
-}
\ No newline at end of file
+
+    /// Inserts `text` at once, for edits that should not look typed. Backends
+    /// that cannot paste type it as fast as they can.
+    fn paste(&mut self, text: &str) -> Result<()> {
+        text.chars().try_for_each(|ch| self.send_char(ch))
+    }
+
+    /// Sends a key combo, assumed to reset the target (see `--reset`).
+    fn combo(&mut self, combo: &KeyCombo) -> Result<()> {
+        anyhow::bail!("the {} backend cannot send key combos like {combo:?}", self.name())
+    }
+
+    /// Switches output to the named target (`""` is the default one). Only
+    /// [`super::router::Router`] knows more than the default.
+    fn target(&mut self, name: &str) -> Result<()> {
+        if !name.is_empty() {
+            anyhow::bail!("the {} backend has no output target {name:?}", self.name());
+        }
+        Ok(())
+    }
+
+    /// Backend name used in latency reports.
+    fn name(&self) -> &'static str { "unknown" }
+}
+
+impl<I: OutputInjector + ?Sized> OutputInjector for Box<I> {
+    fn send_char(&mut self, ch: char) -> Result<()> { (**self).send_char(ch) }
+    fn backspace(&mut self) -> Result<()> { (**self).backspace() }
+    fn paste(&mut self, text: &str) -> Result<()> { (**self).paste(text) }
+    fn combo(&mut self, combo: &KeyCombo) -> Result<()> { (**self).combo(combo) }
+    fn target(&mut self, name: &str) -> Result<()> { (**self).target(name) }
+    fn name(&self) -> &'static str { (**self).name() }
+}
@@ -0,0 +1,110 @@
+use super::injector::OutputInjector;
+use super::reset::{ComboKey, KeyCombo, Modifier};
+use crate::prelude::*;
+use std::process::Command;
+
+/// Types into a running Neovim through its server socket
+/// (`nvim --listen <socket>`).
+pub struct NvimInjector {
+    server: String,
+}
+
+impl NvimInjector {
+    pub fn new(server: &str) -> Self {
+        Self { server: server.to_string() }
+    }
+
+    fn nvim(&self, flag: &str, arg: &str) -> Result<()> {
+        let out = Command::new("nvim")
+            .args(["--server", &self.server, flag, arg])
+            .output()
+            .map_err(|e| anyhow::anyhow!("nvim: {e}"))?;
+        if !out.status.success() {
+            anyhow::bail!("nvim {flag} ({}): {}", self.server, String::from_utf8_lossy(&out.stderr).trim());
+        }
+        Ok(())
+    }
+}
+
+/// `ch` in Neovim key notation.
+fn key(ch: char) -> String {
+    match ch {
+        '<' => "<lt>".to_string(),
+        '\n' | '\r' => "<CR>".to_string(),
+        '\t' => "<Tab>".to_string(),
+        _ => ch.to_string(),
+    }
+}
+
+/// `combo` in Neovim key notation, e.g. `<C-a>`.
+fn combo_keys(combo: &KeyCombo) -> String {
+    let name = match combo.key {
+        ComboKey::Char(c) if combo.mods.is_empty() => return key(c),
+        ComboKey::Char('<') => "lt".to_string(),
+        ComboKey::Char(c) => c.to_string(),
+        ComboKey::Backspace => "BS".to_string(),
+        ComboKey::Delete => "Del".to_string(),
+        ComboKey::Escape => "Esc".to_string(),
+        ComboKey::Enter => "CR".to_string(),
+        ComboKey::Tab => "Tab".to_string(),
+        ComboKey::Home => "Home".to_string(),
+        ComboKey::End => "End".to_string(),
+    };
+    let mods: String = combo
+        .mods
+        .iter()
+        .map(|m| match m {
+            Modifier::Ctrl => "C-",
+            Modifier::Alt => "M-",
+            Modifier::Shift => "S-",
+            Modifier::Meta => "D-",
+        })
+        .collect();
+    format!("<{mods}{name}>")
+}
+
+/// `text` as a double-quoted Vim string literal.
+fn vim_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '\\' => out += "\\\\",
+            '"' => out += "\\\"",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl OutputInjector for NvimInjector {
+    fn send_char(&mut self, ch: char) -> Result<()> { self.nvim("--remote-send", &key(ch)) }
+
+    fn backspace(&mut self) -> Result<()> { self.nvim("--remote-send", "<BS>") }
+
+    /// With `nvim_paste`, so the text is not auto-indented or remapped.
+    fn paste(&mut self, text: &str) -> Result<()> {
+        self.nvim("--remote-expr", &format!("nvim_paste({}, v:false, -1)", vim_string(text)))
+    }
+
+    fn combo(&mut self, combo: &KeyCombo) -> Result<()> { self.nvim("--remote-send", &combo_keys(combo)) }
+
+    fn name(&self) -> &'static str { "nvim" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_keys_and_strings() {
+        assert_eq!("a<b\n\t".chars().map(key).collect::<String>(), "a<lt>b<CR><Tab>");
+        assert_eq!(combo_keys(&"ctrl+w".parse().unwrap()), "<C-w>");
+        assert_eq!(combo_keys(&"alt+shift+backspace".parse().unwrap()), "<M-S-BS>");
+        assert_eq!(combo_keys(&"esc".parse().unwrap()), "<Esc>");
+        assert_eq!(vim_string("say \"hi\"\\\n"), r#""say \"hi\"\\\n""#);
+    }
+}
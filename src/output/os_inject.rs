@@ -1,4 +1,5 @@
 use super::injector::OutputInjector;
+use super::reset::{ComboKey, KeyCombo, Modifier};
 use crate::prelude::*;
 use enigo::Keyboard;
 use enigo::{Direction, Enigo, Key, Settings};
@@ -12,6 +13,25 @@ impl OsInjector {
         let enigo = Enigo::new(&Settings::default()).expect("failed to create Enigo");
         Self { enigo }
     }
+
+    /// Types `text` as fast as the OS accepts it: one `text` call per run
+    /// between newlines and tabs, instead of one per character. The target
+    /// still sees keystrokes, so it may auto-indent or complete them.
+    pub fn type_fast(&mut self, text: &str) -> Result<()> {
+        for run in text.split_inclusive(['\n', '\r', '\t']) {
+            let (body, sep) = match run.chars().next_back() {
+                Some(c @ ('\n' | '\r' | '\t')) => (&run[..run.len() - 1], Some(c)),
+                _ => (run, None),
+            };
+            if !body.is_empty() {
+                self.enigo.text(body)?;
+            }
+            if let Some(c) = sep {
+                self.send_char(c)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl OutputInjector for OsInjector {
@@ -31,4 +51,41 @@ impl OutputInjector for OsInjector {
         self.enigo.key(Key::Backspace, Direction::Click)?;
         Ok(())
     }
+
+    /// Not a real paste: that would go through the user's clipboard. The
+    /// text is typed with [`OsInjector::type_fast`] instead.
+    fn paste(&mut self, text: &str) -> Result<()> { self.type_fast(text) }
+
+    fn combo(&mut self, combo: &KeyCombo) -> Result<()> {
+        let mods: Vec<Key> = combo
+            .mods
+            .iter()
+            .map(|m| match m {
+                Modifier::Ctrl => Key::Control,
+                Modifier::Alt => Key::Alt,
+                Modifier::Shift => Key::Shift,
+                Modifier::Meta => Key::Meta,
+            })
+            .collect();
+        let key = match combo.key {
+            ComboKey::Char(c) => Key::Unicode(c),
+            ComboKey::Backspace => Key::Backspace,
+            ComboKey::Delete => Key::Delete,
+            ComboKey::Escape => Key::Escape,
+            ComboKey::Enter => Key::Return,
+            ComboKey::Tab => Key::Tab,
+            ComboKey::Home => Key::Home,
+            ComboKey::End => Key::End,
+        };
+        for m in &mods {
+            self.enigo.key(*m, Direction::Press)?;
+        }
+        let clicked = self.enigo.key(key, Direction::Click);
+        for m in mods.iter().rev() {
+            self.enigo.key(*m, Direction::Release)?;
+        }
+        Ok(clicked?)
+    }
+
+    fn name(&self) -> &'static str { "os" }
 }
@@ -0,0 +1,97 @@
+use crate::prelude::*;
+use std::str::FromStr;
+
+/// How `--loop` puts the target back to its initial state between rounds.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Reset {
+    /// Backspace over everything the shadow buffer says was typed.
+    #[default]
+    Erase,
+    /// Send these key combos, e.g. `ctrl+a backspace`.
+    Keys(Vec<KeyCombo>),
+}
+
+impl FromStr for Reset {
+    type Err = anyhow::Error;
+
+    /// `erase`, or key combos separated by spaces or commas.
+    fn from_str(s: &str) -> Result<Self> {
+        if s.trim().eq_ignore_ascii_case("erase") {
+            return Ok(Self::Erase);
+        }
+        let combos = s.split([' ', ',']).filter(|c| !c.is_empty()).map(str::parse).collect::<Result<Vec<_>>>()?;
+        if combos.is_empty() {
+            anyhow::bail!("reset: expected erase or key combos like ctrl+a backspace");
+        }
+        Ok(Self::Keys(combos))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier { Ctrl, Alt, Shift, Meta }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComboKey { Char(char), Backspace, Delete, Escape, Enter, Tab, Home, End }
+
+/// A key pressed while holding modifiers, e.g. `ctrl+shift+k`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub mods: Vec<Modifier>,
+    pub key: ComboKey,
+}
+
+impl FromStr for KeyCombo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let key = parts.pop().unwrap_or_default();
+        let mods = parts
+            .iter()
+            .map(|m| match m.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Ok(Modifier::Ctrl),
+                "alt" | "option" => Ok(Modifier::Alt),
+                "shift" => Ok(Modifier::Shift),
+                "meta" | "cmd" | "super" => Ok(Modifier::Meta),
+                other => Err(anyhow::anyhow!("key combo {s:?}: unknown modifier {other:?}")),
+            })
+            .collect::<Result<_>>()?;
+        let mut chars = key.chars();
+        let key = match (chars.next(), chars.next()) {
+            (Some(c), None) => ComboKey::Char(c.to_ascii_lowercase()),
+            _ => match key.to_ascii_lowercase().as_str() {
+                "backspace" => ComboKey::Backspace,
+                "delete" | "del" => ComboKey::Delete,
+                "escape" | "esc" => ComboKey::Escape,
+                "enter" | "return" => ComboKey::Enter,
+                "tab" => ComboKey::Tab,
+                "home" => ComboKey::Home,
+                "end" => ComboKey::End,
+                other => anyhow::bail!("key combo {s:?}: unknown key {other:?}"),
+            },
+        };
+        Ok(Self { mods, key })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_erase_and_key_combos() {
+        assert_eq!("erase".parse::<Reset>().unwrap(), Reset::Erase);
+        let Reset::Keys(keys) = "ctrl+A, backspace  esc".parse::<Reset>().unwrap() else { panic!() };
+        assert_eq!(
+            keys,
+            [
+                KeyCombo { mods: vec![Modifier::Ctrl], key: ComboKey::Char('a') },
+                KeyCombo { mods: vec![], key: ComboKey::Backspace },
+                KeyCombo { mods: vec![], key: ComboKey::Escape },
+            ]
+        );
+        assert!("hyper+x".parse::<Reset>().is_err());
+        assert!("ctrl+pgup".parse::<Reset>().is_err());
+        assert!("".parse::<Reset>().is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use super::injector::OutputInjector;
+use super::reset::KeyCombo;
+use crate::prelude::*;
+
+/// Sends output to one of several named injectors, switched by
+/// [`OutputInjector::target`]. The default output is registered as `""`.
+pub struct Router {
+    targets: Vec<(String, Box<dyn OutputInjector>)>,
+    current: usize,
+}
+
+impl Router {
+    pub fn new(default: Box<dyn OutputInjector>) -> Self {
+        Self { targets: vec![(String::new(), default)], current: 0 }
+    }
+
+    pub fn add(&mut self, name: &str, injector: Box<dyn OutputInjector>) {
+        match self.targets.iter_mut().find(|(n, _)| n == name) {
+            Some((_, slot)) => *slot = injector,
+            None => self.targets.push((name.to_string(), injector)),
+        }
+    }
+
+    fn inner(&mut self) -> &mut Box<dyn OutputInjector> { &mut self.targets[self.current].1 }
+}
+
+impl OutputInjector for Router {
+    fn send_char(&mut self, ch: char) -> Result<()> { self.inner().send_char(ch) }
+    fn backspace(&mut self) -> Result<()> { self.inner().backspace() }
+    fn paste(&mut self, text: &str) -> Result<()> { self.inner().paste(text) }
+    fn combo(&mut self, combo: &KeyCombo) -> Result<()> { self.inner().combo(combo) }
+
+    fn target(&mut self, name: &str) -> Result<()> {
+        self.current = self
+            .targets
+            .iter()
+            .position(|(n, _)| n == name)
+            .ok_or_else(|| anyhow::anyhow!("unknown output target {name:?}"))?;
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str { self.targets[self.current].1.name() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    struct Tape(Rc<RefCell<String>>);
+
+    impl OutputInjector for Tape {
+        fn send_char(&mut self, ch: char) -> Result<()> { self.0.borrow_mut().push(ch); Ok(()) }
+        fn backspace(&mut self) -> Result<()> { self.0.borrow_mut().pop(); Ok(()) }
+    }
+
+    #[test]
+    fn switches_between_named_targets() {
+        let (main, shell) = (Rc::new(RefCell::new(String::new())), Rc::new(RefCell::new(String::new())));
+        let mut router = Router::new(Box::new(Tape(main.clone())));
+        router.add("shell", Box::new(Tape(shell.clone())));
+        router.paste("fn").unwrap();
+        router.target("shell").unwrap();
+        router.paste("ls").unwrap();
+        router.backspace().unwrap();
+        router.target("").unwrap();
+        router.send_char('!').unwrap();
+        assert_eq!((main.borrow().as_str(), shell.borrow().as_str()), ("fn!", "l"));
+        assert!(router.target("editor").unwrap_err().to_string().contains("unknown output target"));
+    }
+}
@@ -0,0 +1,87 @@
+use super::injector::OutputInjector;
+use super::reset::{ComboKey, KeyCombo, Modifier};
+use crate::prelude::*;
+use std::process::Command;
+
+/// Types into a tmux pane with `tmux send-keys`.
+pub struct TmuxInjector {
+    pane: String,
+}
+
+impl TmuxInjector {
+    pub fn new(pane: &str) -> Self {
+        Self { pane: pane.to_string() }
+    }
+
+    fn tmux(&self, args: &[&str]) -> Result<()> {
+        let out = Command::new("tmux").args(args).output().map_err(|e| anyhow::anyhow!("tmux: {e}"))?;
+        if !out.status.success() {
+            anyhow::bail!("tmux {}: {}", args[0], String::from_utf8_lossy(&out.stderr).trim());
+        }
+        Ok(())
+    }
+
+    fn send_keys(&self, key: &str) -> Result<()> {
+        self.tmux(&["send-keys", "-t", &self.pane, key])
+    }
+}
+
+/// tmux key name for a combo, e.g. `C-a` or `M-BSpace`.
+fn key_name(combo: &KeyCombo) -> Result<String> {
+    let mut name = String::new();
+    for m in &combo.mods {
+        name += match m {
+            Modifier::Ctrl => "C-",
+            Modifier::Alt => "M-",
+            Modifier::Shift => "S-",
+            Modifier::Meta => anyhow::bail!("tmux has no meta modifier"),
+        };
+    }
+    match combo.key {
+        ComboKey::Char(c) => name.push(c),
+        ComboKey::Backspace => name += "BSpace",
+        ComboKey::Delete => name += "DC",
+        ComboKey::Escape => name += "Escape",
+        ComboKey::Enter => name += "Enter",
+        ComboKey::Tab => name += "Tab",
+        ComboKey::Home => name += "Home",
+        ComboKey::End => name += "End",
+    }
+    Ok(name)
+}
+
+impl OutputInjector for TmuxInjector {
+    fn send_char(&mut self, ch: char) -> Result<()> {
+        match ch {
+            '\n' | '\r' => self.send_keys("Enter"),
+            '\t' => self.send_keys("Tab"),
+            _ => self.tmux(&["send-keys", "-t", &self.pane, "-l", "--", &ch.to_string()]),
+        }
+    }
+
+    fn backspace(&mut self) -> Result<()> { self.send_keys("BSpace") }
+
+    /// Through a tmux buffer, which the pane receives as one paste.
+    fn paste(&mut self, text: &str) -> Result<()> {
+        self.tmux(&["set-buffer", "-b", "monkey-typer", "--", text])?;
+        self.tmux(&["paste-buffer", "-d", "-b", "monkey-typer", "-t", &self.pane])
+    }
+
+    fn combo(&mut self, combo: &KeyCombo) -> Result<()> { self.send_keys(&key_name(combo)?) }
+
+    fn name(&self) -> &'static str { "tmux" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combos_use_tmux_key_names() {
+        let name = |s: &str| key_name(&s.parse().unwrap());
+        assert_eq!(name("ctrl+a").unwrap(), "C-a");
+        assert_eq!(name("alt+shift+backspace").unwrap(), "M-S-BSpace");
+        assert_eq!(name("delete").unwrap(), "DC");
+        assert!(name("cmd+k").is_err());
+    }
+}
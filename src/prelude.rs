@@ -1,2 +1,2 @@
 pub use anyhow::Result;
-pub use tracing::debug;
+pub use tracing::{debug, info, warn};
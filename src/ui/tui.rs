@@ -0,0 +1,240 @@
+use crate::engine::{controller::Controller, stats::Summary};
+use crate::format::tks_json::Action;
+use crate::{output::injector::OutputInjector, prelude::*};
+use crossterm::{
+    cursor, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, ClearType},
+};
+use std::io::Write;
+use std::time::Duration;
+
+const PREVIEW_ACTIONS: usize = 120;
+
+/// Everything the presenter view shows, captured from the controller.
+#[derive(Debug, Clone, Default)]
+pub struct Frame {
+    pub title: String,
+    pub typed: String,
+    /// Upcoming actions rendered as text, backspaces as `⌫`.
+    pub upcoming: String,
+    pub pos: usize,
+    pub end: usize,
+    pub started: bool,
+    pub paused: bool,
+    pub marker: Option<String>,
+    pub note: Option<String>,
+    pub next_note: Option<String>,
+    pub actions_per_key: usize,
+    pub elapsed: Duration,
+    pub last_ms: Option<f64>,
+    pub latency: Option<Summary>,
+    pub message: Option<String>,
+}
+
+impl Frame {
+    pub fn capture<I: OutputInjector>(title: &str, c: &Controller<I>) -> Self {
+        let status = c.status();
+        let upcoming = c.engine
+            .upcoming(PREVIEW_ACTIONS)
+            .into_iter()
+            .map(|a| match a {
+                Action::Char(ch) => ch,
+                Action::Backspace => '⌫',
+            })
+            .collect();
+        Self {
+            title: title.to_string(),
+            typed: c.engine.shadow.text().to_string(),
+            upcoming,
+            pos: status.pos,
+            end: status.end,
+            started: status.started,
+            paused: status.paused,
+            marker: status.marker,
+            note: status.note,
+            next_note: status.next_note,
+            actions_per_key: c.actions_per_key,
+            elapsed: c.started_at.map(|t| t.elapsed()).unwrap_or_default(),
+            last_ms: c.stats.last().map(|s| s.total_ms()),
+            latency: c.stats.total(),
+            message: c.message.clone(),
+        }
+    }
+}
+
+/// Full-screen presenter view on the controlling terminal (alternate screen).
+pub struct Tui<W: Write> {
+    out: W,
+    size: Option<(u16, u16)>,
+    entered: bool,
+}
+
+impl Tui<std::io::Stdout> {
+    pub fn enter() -> Result<Self> {
+        let mut out = std::io::stdout();
+        queue!(out, terminal::EnterAlternateScreen, cursor::Hide)?;
+        out.flush()?;
+        Ok(Self { out, size: None, entered: true })
+    }
+}
+
+impl<W: Write> Tui<W> {
+    /// Renders into `out` with a fixed size, without touching the terminal.
+    #[cfg(test)]
+    pub fn headless(out: W, width: u16, height: u16) -> Self {
+        Self { out, size: Some((width, height)), entered: false }
+    }
+
+    /// Redraws in place, clearing only the rest of each row, so the
+    /// periodic refresh does not flicker.
+    pub fn draw(&mut self, f: &Frame) -> Result<()> {
+        let (w, h) = match self.size {
+            Some(size) => size,
+            None => terminal::size()?,
+        };
+        let w = w as usize;
+        let h = h.max(8);
+
+        // header
+        let state = match (f.started, f.paused) {
+            (false, _) => "READY",
+            (true, true) => "PAUSED",
+            (true, false) => "RUNNING",
+        };
+        let pct = (f.pos * 100).checked_div(f.end).unwrap_or(100);
+        let secs = f.elapsed.as_secs();
+        let header = format!(
+            " {}  [{}]  {}/{} ({}%)  marker: {}  mode: {}/key  {:02}:{:02}:{:02}",
+            f.title,
+            state,
+            f.pos,
+            f.end,
+            pct,
+            f.marker.as_deref().unwrap_or("-"),
+            f.actions_per_key,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+        );
+        self.line(0, &header, Some(Attribute::Reverse), w)?;
+        let filled = (w * f.pos).checked_div(f.end).unwrap_or(w).min(w);
+        self.line(1, &format!("{}{}", "█".repeat(filled), "░".repeat(w - filled)), None, w)?;
+
+        // typed text, scrolled to its tail, followed by the preview
+        let body_rows = (h - 5) as usize;
+        let typed_lines: Vec<&str> = f.typed.split('\n').collect();
+        let skip = typed_lines.len().saturating_sub(body_rows);
+        let mut next_row = 2;
+        for (row, (i, l)) in (2u16..).zip(typed_lines.iter().enumerate().skip(skip)) {
+            next_row = row + 1;
+            queue!(self.out, cursor::MoveTo(0, row), Print(clip(l, w)))?;
+            if i + 1 == typed_lines.len() {
+                let col = l.chars().count().min(w);
+                let preview = f.upcoming.split('\n').next().unwrap_or_default();
+                let mut chars = preview.chars();
+                if let Some(next) = chars.next() {
+                    queue!(
+                        self.out,
+                        cursor::MoveTo(col as u16, row),
+                        SetAttribute(Attribute::Reverse),
+                        Print(next),
+                        SetAttribute(Attribute::Reset),
+                        SetAttribute(Attribute::Dim),
+                        Print(clip(chars.as_str(), w.saturating_sub(col + 1))),
+                        SetAttribute(Attribute::Reset),
+                    )?;
+                } else if f.upcoming.starts_with('\n') {
+                    queue!(
+                        self.out,
+                        cursor::MoveTo(col as u16, row),
+                        SetAttribute(Attribute::Reverse),
+                        Print('⏎'),
+                        SetAttribute(Attribute::Reset),
+                    )?;
+                }
+            }
+            queue!(self.out, terminal::Clear(ClearType::UntilNewLine))?;
+        }
+        for row in next_row..h - 2 {
+            queue!(self.out, cursor::MoveTo(0, row), terminal::Clear(ClearType::UntilNewLine))?;
+        }
+
+        // footer
+        let note = format!(
+            " Note: {}   Next: {}",
+            f.note.as_deref().unwrap_or("-"),
+            f.next_note.as_deref().unwrap_or("-")
+        );
+        self.line(h - 2, &note, Some(Attribute::Bold), w)?;
+        let latency = match (&f.latency, f.last_ms) {
+            (Some(l), Some(last)) => format!(
+                "last {:.2} / p50 {:.2} / p95 {:.2} / p99 {:.2} / max {:.2} ms, jitter {:.2}",
+                last, l.p50, l.p95, l.p99, l.max, l.jitter
+            ),
+            _ => "-".to_string(),
+        };
+        let stats = format!(" Latency {}   {}", latency, f.message.as_deref().unwrap_or(""));
+        self.line(h - 1, &stats, Some(Attribute::Dim), w)?;
+        self.out.flush()?;
+        Ok(())
+    }
+
+    fn line(&mut self, row: u16, text: &str, attr: Option<Attribute>, w: usize) -> Result<()> {
+        queue!(self.out, cursor::MoveTo(0, row))?;
+        if let Some(attr) = attr {
+            queue!(self.out, SetAttribute(attr))?;
+        }
+        queue!(self.out, Print(clip(text, w)), SetAttribute(Attribute::Reset), terminal::Clear(ClearType::UntilNewLine))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for Tui<W> {
+    fn drop(&mut self) {
+        if self.entered {
+            let _ = queue!(self.out, cursor::Show, terminal::LeaveAlternateScreen);
+            let _ = self.out.flush();
+        }
+    }
+}
+
+fn clip(s: &str, w: usize) -> String {
+    s.chars().filter(|c| *c != '\r').take(w).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_progress_text_and_preview() {
+        let frame = Frame {
+            title: "demo.tks.json".into(),
+            typed: "fn main() {\n    pri".into(),
+            upcoming: "ntln!(\"hi\");\n}".into(),
+            pos: 19,
+            end: 38,
+            started: true,
+            marker: Some("intro".into()),
+            note: Some("Say hi".into()),
+            actions_per_key: 2,
+            last_ms: Some(0.4),
+            latency: Summary::of([0.4, 0.5, 0.6]),
+            ..Default::default()
+        };
+        let mut tui = Tui::headless(Vec::new(), 100, 12);
+        tui.draw(&frame).unwrap();
+        let screen = String::from_utf8_lossy(&tui.out).into_owned();
+
+        assert!(screen.contains("[RUNNING]  19/38 (50%)"));
+        assert!(screen.contains("marker: intro"));
+        assert!(screen.contains("mode: 2/key"));
+        assert!(screen.contains("    pri"));
+        assert!(screen.contains("tln!(\"hi\");"));
+        assert!(screen.contains("Note: Say hi"));
+        assert!(screen.contains("last 0.40 / p50 0.50 / p95 0.60"));
+        assert!(!screen.contains("\x1b[2J"), "no full clear on redraw");
+        assert!(screen.contains("\x1b[K"));
+    }
+}